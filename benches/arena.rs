@@ -0,0 +1,76 @@
+// Counts heap allocations per transaction for the cloning write path
+// (`load_account_for_write` + `commit_transaction`) and the arena path
+// (`load_account_for_write_in` + `commit_scratch`), and times both.
+//
+// Run with `cargo bench --features alloc-tracking --bench arena`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use sonic_test::account_state_management::{AccountState, AccountsDb};
+use sonic_test::pubkey::Pubkey;
+use sonic_test::unsafe_rust_memory_management::alloc_tracking::{TrackingAlloc, count_allocations};
+use sonic_test::unsafe_rust_memory_management::arena::Arena;
+
+const TRANSACTIONS: u64 = 100_000;
+const ACCOUNTS: u8 = 4;
+const DATA_LEN: usize = 1024;
+
+#[global_allocator]
+static GLOBAL: TrackingAlloc = TrackingAlloc::system();
+
+fn accounts_db() -> (AccountsDb, Vec<Pubkey>) {
+    let db = AccountsDb::new();
+    let pubkeys: Vec<_> = (1..=ACCOUNTS).map(|i| Pubkey::new([i; 32])).collect();
+    for pubkey in &pubkeys {
+        db.create_account(*pubkey, AccountState::new(u64::MAX / 2, vec![0xAB; DATA_LEN], Pubkey::default()));
+    }
+    (db, pubkeys)
+}
+
+fn bench(name: &str, mut transaction: impl FnMut(u64)) {
+    // Warm up so one-off growth of the maps and arena is not counted
+    for i in 0..1000 {
+        transaction(i);
+    }
+
+    let start = Instant::now();
+    let ((), stats) = count_allocations(|| {
+        for i in 0..TRANSACTIONS {
+            transaction(black_box(i));
+        }
+    });
+    let elapsed = start.elapsed();
+    let allocations = stats.allocations as f64 / TRANSACTIONS as f64;
+    let bytes = stats.bytes as f64 / TRANSACTIONS as f64;
+
+    let per_tx = elapsed.as_nanos() as f64 / TRANSACTIONS as f64;
+    println!("{name:<24} {per_tx:>8.0} ns/tx {allocations:>8.2} allocs/tx {bytes:>10.0} bytes/tx");
+}
+
+fn main() {
+    println!("{ACCOUNTS} writable accounts of {DATA_LEN} bytes per transaction");
+
+    let (db, pubkeys) = accounts_db();
+    bench("cloned AccountState", |i| {
+        let tx = db.begin_transaction(0, i as u32);
+        for pubkey in &pubkeys {
+            let mut guard = db.load_account_for_write(pubkey, &tx).unwrap();
+            guard.transfer_lamports(1).unwrap();
+        }
+        db.commit_transaction(tx).unwrap();
+    });
+
+    let (db, pubkeys) = accounts_db();
+    let mut arena = Arena::new();
+    bench("arena scratch copies", |i| {
+        let tx = db.begin_transaction(0, i as u32);
+        let mut guards = [const { None }; ACCOUNTS as usize];
+        for (guard, pubkey) in guards.iter_mut().zip(&pubkeys) {
+            let loaded = guard.insert(db.load_account_for_write_in(pubkey, &tx, &arena).unwrap());
+            loaded.transfer_lamports(1).unwrap();
+        }
+        db.commit_scratch(tx, &guards.map(Option::unwrap)).unwrap();
+        arena.reset();
+    });
+}
@@ -0,0 +1,102 @@
+// Moves values from producer threads to a consumer through the `spsc` and
+// `mpmc` ring buffers and through `std::sync::mpsc::sync_channel` with the
+// same capacity, and reports the throughput of each.
+//
+// Run with `cargo bench --bench ring_buffer`.
+
+use std::hint::black_box;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sonic_test::unsafe_rust_memory_management::ring_buffer::{mpmc, spsc};
+
+const MESSAGES: u64 = 4_000_000;
+const CAPACITY: usize = 1024;
+const PRODUCERS: u64 = 4;
+
+fn report(name: &str, elapsed: Duration) {
+    let per_msg = elapsed.as_nanos() as f64 / MESSAGES as f64;
+    let throughput = MESSAGES as f64 / elapsed.as_secs_f64() / 1e6;
+    println!("{name:<32} {elapsed:>10.2?} total {per_msg:>8.2} ns/msg {throughput:>8.2} M msg/s");
+}
+
+// Runs `producers` threads that together send `MESSAGES` values while the
+// calling thread receives them, and checks nothing was lost
+fn bench<S: Send + 'static>(
+    name: &str,
+    producers: u64,
+    sender: S,
+    send: fn(&S, u64),
+    clone: fn(&S) -> S,
+    mut recv: impl FnMut() -> Option<u64>,
+) {
+    let per_producer = MESSAGES / producers;
+    let start = Instant::now();
+    let handles: Vec<_> = (0..producers)
+        .map(|_| {
+            let sender = clone(&sender);
+            thread::spawn(move || {
+                for i in 0..per_producer {
+                    send(&sender, black_box(i));
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut sum = 0u64;
+    let mut received = 0u64;
+    while let Some(value) = recv() {
+        sum = sum.wrapping_add(value);
+        received += 1;
+    }
+    let elapsed = start.elapsed();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(received, per_producer * producers);
+    assert_eq!(sum, producers * per_producer * (per_producer - 1) / 2);
+    report(name, elapsed);
+}
+
+fn main() {
+    println!("1 producer, 1 consumer, capacity {CAPACITY}");
+
+    // The spsc sender cannot be cloned, so it is moved into the one producer
+    let (tx, rx) = spsc::channel::<u64>(CAPACITY);
+    let start = Instant::now();
+    let producer = thread::spawn(move || {
+        for i in 0..MESSAGES {
+            tx.send(black_box(i)).unwrap();
+        }
+    });
+    let mut sum = 0u64;
+    while let Ok(value) = rx.recv() {
+        sum = sum.wrapping_add(value);
+    }
+    let elapsed = start.elapsed();
+    producer.join().unwrap();
+    assert_eq!(sum, MESSAGES * (MESSAGES - 1) / 2);
+    report("spsc", elapsed);
+
+    let (tx, rx) = mpmc::channel::<u64>(CAPACITY);
+    bench("mpmc", 1, tx, |tx, i| tx.send(i).unwrap(), Clone::clone, || rx.recv().ok());
+
+    let (tx, rx) = mpsc::sync_channel::<u64>(CAPACITY);
+    bench("std::sync::mpsc::sync_channel", 1, tx, |tx, i| tx.send(i).unwrap(), Clone::clone, || {
+        rx.recv().ok()
+    });
+
+    println!();
+    println!("{PRODUCERS} producers, 1 consumer, capacity {CAPACITY}");
+
+    let (tx, rx) = mpmc::channel::<u64>(CAPACITY);
+    bench("mpmc", PRODUCERS, tx, |tx, i| tx.send(i).unwrap(), Clone::clone, || rx.recv().ok());
+
+    let (tx, rx) = mpsc::sync_channel::<u64>(CAPACITY);
+    bench("std::sync::mpsc::sync_channel", PRODUCERS, tx, |tx, i| tx.send(i).unwrap(), Clone::clone, || {
+        rx.recv().ok()
+    });
+}
@@ -0,0 +1,105 @@
+// Measures `Account::from_bytes` on aligned and misaligned input, and the
+// packed `PackedAccount::from_bytes` parser for comparison.
+//
+// `Account` used to have an aligned fast path that cast the input to a
+// header of native `u64`/`u32` fields, next to a slower unaligned mode. The
+// headers now use alignment-1 little-endian fields, so there is a single
+// parser. `NativeHeader` below re-creates the old aligned overlay to show
+// what the single parser costs against it.
+//
+// Run with `cargo bench --bench zero_copy`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use sonic_test::unsafe_rust_memory_management::AlignedBuffer;
+use sonic_test::zero_copy_deserialization::{Account, AccountHeader, AccountWriter, LayoutVersion, PackedAccount};
+
+const ITERATIONS: u32 = 10_000_000;
+const DATA: [u8; 64] = [0xAB; 64];
+
+// Stands in for the aligned fast path that the endian wrappers removed from
+// `Account`, so the aligned-vs-unaligned comparison still has an aligned
+// side. It is the header as it was before them: native integers, so it
+// needs 8-byte alignment, and the same layout as `AccountHeader` on
+// little-endian hosts.
+#[repr(C)]
+struct NativeHeader {
+    discriminator: u8,
+    owner: [u8; 32],
+    version: u8,
+    _padding0: [u8; 6],
+    amount: u64,
+    data_len: u32,
+    _padding1: [u8; 4],
+}
+
+const _: () = assert!(size_of::<NativeHeader>() == AccountHeader::SIZE);
+const _: () = assert!(align_of::<NativeHeader>() == 8);
+
+// The old aligned fast path: check size and alignment, then cast
+fn parse_native(bytes: &[u8]) -> Option<(&NativeHeader, &[u8])> {
+    if bytes.len() < size_of::<NativeHeader>() || !(bytes.as_ptr() as usize).is_multiple_of(align_of::<NativeHeader>()) {
+        return None;
+    }
+    // Size and alignment were checked, and every bit pattern is valid
+    let header = unsafe { &*bytes.as_ptr().cast::<NativeHeader>() };
+    let data = bytes.get(size_of::<NativeHeader>()..)?.get(..header.data_len as usize)?;
+    Some((header, data))
+}
+
+fn bench(name: &str, mut f: impl FnMut() -> u64) {
+    let start = Instant::now();
+    let mut acc = 0u64;
+    for _ in 0..ITERATIONS {
+        acc = acc.wrapping_add(f());
+    }
+    let elapsed = start.elapsed();
+    black_box(acc);
+
+    let per_iter = elapsed.as_nanos() as f64 / ITERATIONS as f64;
+    println!("{name:<32} {:>10.2?} total {per_iter:>8.2} ns/parse", elapsed);
+}
+
+fn main() {
+    let writer = AccountWriter::new().discriminator(1).amount(42).data(&DATA);
+
+    let mut storage = AlignedBuffer::new(writer.encoded_len(), 8);
+    let aligned = &mut storage[..];
+    writer.write(aligned).unwrap();
+    let aligned = &*aligned;
+    bench("from_bytes (aligned)", || {
+        let account = Account::from_bytes(black_box(aligned)).unwrap();
+        account.amount() + account.data().len() as u64
+    });
+
+    // Only meaningful where native and little-endian byte order agree
+    if cfg!(target_endian = "little") {
+        let v0 = writer.version(LayoutVersion::V0);
+        let mut storage = AlignedBuffer::new(v0.encoded_len(), 8);
+        v0.write(&mut storage).unwrap();
+        let native = &storage[..];
+        bench("native overlay (aligned)", || {
+            let (header, data) = parse_native(black_box(native)).unwrap();
+            header.amount + data.len() as u64
+        });
+    }
+
+    let mut storage = AlignedBuffer::new(writer.encoded_len() + 3, 8);
+    let misaligned = &mut storage[3..];
+    writer.write(misaligned).unwrap();
+    let misaligned = &*misaligned;
+    bench("from_bytes (offset 3)", || {
+        let account = Account::from_bytes(black_box(misaligned)).unwrap();
+        account.amount() + account.data().len() as u64
+    });
+
+    let mut storage = AlignedBuffer::new(writer.packed_len() + 3, 8);
+    let packed = &mut storage[3..];
+    writer.write_packed(packed).unwrap();
+    let packed = &*packed;
+    bench("PackedAccount (offset 3)", || {
+        let account = PackedAccount::from_bytes(black_box(packed)).unwrap();
+        account.amount() + account.data().len() as u64
+    });
+}
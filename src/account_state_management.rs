@@ -3,18 +3,30 @@ use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::fmt;
 
+use crate::endian::{LeU32, LeU64};
+use crate::pubkey::Pubkey;
+use crate::unsafe_rust_memory_management::arena::Arena;
+use crate::zero_copy_deserialization::{
+    Account, AccountIter, AccountType, AccountWriter, LayoutVersion, ParseError, ZeroCopy,
+    RECORD_ALIGNMENT,
+};
+use crate::zero_copy_deserialization::program_input::{
+    account_entry_len, InputAccount, InputAccountView, ProgramInput, SerializedAccountHeader,
+    DUPLICATE_ENTRY_SIZE, NON_DUP_MARKER,
+};
+
 /// Represents the state of a Solana account
 #[derive(Debug, Clone, PartialEq)]
 pub struct AccountState {
     pub lamports: u64,
     pub data: Vec<u8>,
-    pub owner: [u8; 32],
+    pub owner: Pubkey,
     pub executable: bool,
     pub rent_epoch: u64,
 }
 
 impl AccountState {
-    pub fn new(lamports: u64, data: Vec<u8>, owner: [u8; 32]) -> Self {
+    pub fn new(lamports: u64, data: Vec<u8>, owner: Pubkey) -> Self {
         Self {
             lamports,
             data,
@@ -25,6 +37,206 @@ impl AccountState {
     }
 }
 
+impl From<InputAccountView<'_>> for AccountState {
+    fn from(view: InputAccountView<'_>) -> Self {
+        Self {
+            lamports: view.lamports(),
+            data: view.data().to_vec(),
+            owner: *view.owner(),
+            executable: view.executable(),
+            rent_epoch: view.rent_epoch(),
+        }
+    }
+}
+
+/// Discriminator of zero-copy accounts that carry a full `AccountState`
+pub const ACCOUNT_STATE_DISCRIMINATOR: u8 = 0xA5;
+
+/// Current version of the `AccountStateRecordHeader` layout
+pub const ACCOUNT_STATE_LAYOUT_VERSION: u8 = 1;
+
+/// Prefix of the data section of an `AccountState` encoded as a zero-copy
+/// `Account`. Lamports and owner live in the account header; the fields the
+/// header has no room for live here, followed by the account data.
+#[repr(C)]
+#[derive(Debug, ZeroCopy)]
+pub struct AccountStateRecordHeader {
+    pub version: u8,
+    pub executable: u8,
+    _padding: [u8; 6],
+    pub rent_epoch: LeU64,
+    pub pubkey: Pubkey,
+}
+
+/// `AccountType` for accounts encoded by `AccountState::encode`
+pub struct AccountStateRecord;
+
+/// View of the data section of an `AccountStateRecord`
+pub struct AccountStateRecordView<'a> {
+    pub header: &'a AccountStateRecordHeader,
+    pub data: &'a [u8],
+}
+
+impl AccountType for AccountStateRecord {
+    const DISCRIMINATOR: u8 = ACCOUNT_STATE_DISCRIMINATOR;
+    type View<'a> = AccountStateRecordView<'a>;
+
+    fn view(data: &[u8]) -> Result<AccountStateRecordView<'_>, ParseError> {
+        let header = AccountStateRecordHeader::from_bytes(data)?;
+        if header.version != ACCOUNT_STATE_LAYOUT_VERSION {
+            return Err(ParseError::InvalidValue {
+                field: "version",
+                offset: AccountStateRecordHeader::VERSION_OFFSET,
+            });
+        }
+        if header.executable > 1 {
+            return Err(ParseError::InvalidValue {
+                field: "executable",
+                offset: AccountStateRecordHeader::EXECUTABLE_OFFSET,
+            });
+        }
+        Ok(AccountStateRecordView {
+            header,
+            data: &data[AccountStateRecordHeader::SIZE..],
+        })
+    }
+}
+
+/// Only accounts written by `AccountState::encode` convert. Any other
+/// account, such as one written by a plain `AccountWriter`, has nowhere to
+/// store `executable` and `rent_epoch` and fails with
+/// `ParseError::DiscriminatorMismatch`.
+impl TryFrom<Account<'_>> for AccountState {
+    type Error = ParseError;
+
+    fn try_from(account: Account<'_>) -> Result<Self, ParseError> {
+        let record = account.parse_as::<AccountStateRecord>()?;
+        Ok(Self::from_record(&account, &record))
+    }
+}
+
+impl AccountState {
+    // Shared with `load_accounts`, which also needs the record's pubkey
+    fn from_record(account: &Account<'_>, record: &AccountStateRecordView<'_>) -> Self {
+        Self {
+            lamports: account.amount(),
+            data: record.data.to_vec(),
+            owner: *account.owner(),
+            executable: record.header.executable != 0,
+            rent_epoch: record.header.rent_epoch.get(),
+        }
+    }
+
+    /// Bytes needed by `encode`
+    pub fn encoded_len(&self) -> usize {
+        LayoutVersion::CURRENT.header_len() + AccountStateRecordHeader::SIZE + self.data.len()
+    }
+
+    /// Encode as a zero-copy `Account` with discriminator
+    /// `ACCOUNT_STATE_DISCRIMINATOR`. Returns the number of bytes written.
+    pub fn encode(&self, pubkey: &Pubkey, buf: &mut [u8]) -> Result<usize, ParseError> {
+        let len = self.encoded_len();
+        let capacity = buf.len();
+        let out = buf.get_mut(..len).ok_or(ParseError::CapacityExceeded {
+            field: "account",
+            offset: 0,
+            required: len,
+            capacity,
+        })?;
+
+        let header_len = AccountWriter::new()
+            .discriminator(ACCOUNT_STATE_DISCRIMINATOR)
+            .owner(self.owner)
+            .amount(self.lamports)
+            .write_header(out, AccountStateRecordHeader::SIZE + self.data.len())?;
+
+        let (record, data) = out[header_len..].split_at_mut(AccountStateRecordHeader::SIZE);
+        *AccountStateRecordHeader::from_bytes_mut(record)? = AccountStateRecordHeader {
+            version: ACCOUNT_STATE_LAYOUT_VERSION,
+            executable: self.executable as u8,
+            _padding: [0; 6],
+            rent_epoch: LeU64::new(self.rent_epoch),
+            pubkey: *pubkey,
+        };
+        data.copy_from_slice(&self.data);
+
+        Ok(len)
+    }
+}
+
+/// An account as passed to a program invocation
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionAccount<'a> {
+    pub pubkey: Pubkey,
+    pub account: &'a AccountState,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// Serialize accounts into the SBF loader's program input format, which
+/// `zero_copy_deserialization::program_input::ProgramInput` parses.
+/// Repeated pubkeys are written as duplicate entries.
+///
+/// Fails with `ParseError::InvalidValue` when a duplicate's first occurrence
+/// is at an index `dup_info` cannot hold (255 and up, since `0xFF` marks a
+/// non-duplicate), and with `ParseError::DataLengthOutOfRange` when an
+/// account's data does not fit the `u32` original length. Offsets are those
+/// the entry would have had in the output.
+pub fn serialize_program_input(
+    accounts: &[InstructionAccount],
+    instruction_data: &[u8],
+    program_id: &Pubkey,
+) -> Result<Vec<u8>, ParseError> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(accounts.len() as u64).to_le_bytes());
+
+    for (index, entry) in accounts.iter().enumerate() {
+        if let Some(original) = accounts[..index].iter().position(|a| a.pubkey == entry.pubkey) {
+            let dup_info = u8::try_from(original)
+                .ok()
+                .filter(|&dup_info| dup_info != NON_DUP_MARKER)
+                .ok_or(ParseError::InvalidValue { field: "dup_info", offset: buf.len() })?;
+            buf.push(dup_info);
+            buf.resize(buf.len() + DUPLICATE_ENTRY_SIZE - 1, 0);
+            continue;
+        }
+
+        let data_len = entry.account.data.len();
+        let original_data_len = u32::try_from(data_len).map_err(|_| ParseError::DataLengthOutOfRange {
+            offset: buf.len() + SerializedAccountHeader::ORIGINAL_DATA_LEN_OFFSET,
+            length: data_len,
+            min: 0,
+            max: u32::MAX as usize,
+        })?;
+
+        let offset = buf.len();
+        let header = SerializedAccountHeader {
+            dup_info: NON_DUP_MARKER,
+            is_signer: entry.is_signer as u8,
+            is_writable: entry.is_writable as u8,
+            executable: entry.account.executable as u8,
+            original_data_len: LeU32::new(original_data_len),
+            key: entry.pubkey,
+            owner: entry.account.owner,
+            lamports: LeU64::new(entry.account.lamports),
+            data_len: LeU64::new(entry.account.data.len() as u64),
+        };
+
+        // Realloc space and alignment padding stay zeroed
+        let entry_len = account_entry_len(offset, entry.account.data.len());
+        buf.resize(offset + entry_len, 0);
+        *SerializedAccountHeader::from_bytes_mut(&mut buf[offset..]).unwrap() = header;
+        let data_start = offset + SerializedAccountHeader::SIZE;
+        buf[data_start..data_start + entry.account.data.len()].copy_from_slice(&entry.account.data);
+        buf[offset + entry_len - 8..offset + entry_len].copy_from_slice(&entry.account.rent_epoch.to_le_bytes());
+    }
+
+    buf.extend_from_slice(&(instruction_data.len() as u64).to_le_bytes());
+    buf.extend_from_slice(instruction_data);
+    buf.extend_from_slice(program_id.as_bytes());
+    Ok(buf)
+}
+
 /// Transaction ID type
 pub type TransactionId = u64;
 
@@ -36,8 +248,8 @@ pub struct Transaction {
     pub slot: u32,
     pub status: TransactionStatus,
     pub created_at: u64,
-    pub locked_accounts: HashSet<[u8; 32]>,
-    pub modifications: HashMap<[u8; 32], AccountState>, // Original state for rollback
+    pub locked_accounts: HashSet<Pubkey>,
+    pub modifications: HashMap<Pubkey, AccountState>, // Original state for rollback
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -57,6 +269,7 @@ pub enum AccountError {
     InsufficientFunds,
     InvalidAccountData,
     ConcurrentModification,
+    AlreadyLoaded,
 }
 
 impl fmt::Display for AccountError {
@@ -69,13 +282,14 @@ impl fmt::Display for AccountError {
             AccountError::InsufficientFunds => write!(f, "Insufficient funds"),
             AccountError::InvalidAccountData => write!(f, "Invalid account data"),
             AccountError::ConcurrentModification => write!(f, "Concurrent modification detected"),
+            AccountError::AlreadyLoaded => write!(f, "Account is already loaded for write by this transaction"),
         }
     }
 }
 
 /// Write guard for account modifications within a transaction
 pub struct AccountWriteGuard {
-    pubkey: [u8; 32],
+    pubkey: Pubkey,
     account: AccountState,
     transaction_id: TransactionId,
     accounts_db: Arc<AccountsDb>,
@@ -98,11 +312,11 @@ impl AccountWriteGuard {
         self.account.data = data;
     }
 
-    pub fn get_owner(&self) -> [u8; 32] {
+    pub fn get_owner(&self) -> Pubkey {
         self.account.owner
     }
 
-    pub fn set_owner(&mut self, owner: [u8; 32]) {
+    pub fn set_owner(&mut self, owner: Pubkey) {
         self.account.owner = owner;
     }
 
@@ -133,14 +347,95 @@ impl Drop for AccountWriteGuard {
     }
 }
 
+/// Write guard whose working copy lives in a transaction's `Arena`.
+///
+/// `load_account_for_write` clones the account as the working copy, again as
+/// the original for rollback, and again when the guard drops. This guard
+/// copies the data into the arena once and leaves the stored account as the
+/// original: `AccountsDb::commit_scratch` applies the changes, and dropping
+/// the guard without committing discards them. A guard can only be
+/// committed to the `AccountsDb` that created it.
+pub struct ScratchWriteGuard<'a> {
+    pubkey: Pubkey,
+    lamports: u64,
+    owner: Pubkey,
+    data: &'a mut [u8],
+    arena: &'a Arena,
+    transaction_id: TransactionId,
+    accounts_db: &'a AccountsDb,
+}
+
+impl<'a> ScratchWriteGuard<'a> {
+    pub fn pubkey(&self) -> &Pubkey {
+        &self.pubkey
+    }
+
+    pub fn get_lamports(&self) -> u64 {
+        self.lamports
+    }
+
+    pub fn set_lamports(&mut self, lamports: u64) {
+        self.lamports = lamports;
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        self.data
+    }
+
+    pub fn get_data_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    /// Replace the data with a copy of `data` in the arena
+    pub fn set_data(&mut self, data: &[u8]) {
+        self.data = self.arena.alloc_slice_copy(data);
+    }
+
+    pub fn get_owner(&self) -> Pubkey {
+        self.owner
+    }
+
+    pub fn set_owner(&mut self, owner: Pubkey) {
+        self.owner = owner;
+    }
+
+    pub fn transfer_lamports(&mut self, amount: u64) -> Result<(), AccountError> {
+        if self.lamports < amount {
+            return Err(AccountError::InsufficientFunds);
+        }
+        self.lamports -= amount;
+        Ok(())
+    }
+
+    pub fn add_lamports(&mut self, amount: u64) {
+        self.lamports += amount;
+    }
+}
+
+impl Drop for ScratchWriteGuard<'_> {
+    fn drop(&mut self) {
+        // Release the lock unless another transaction has taken it since
+        let mut locks = self.accounts_db.account_locks.write().unwrap();
+        if locks.get(&self.pubkey) == Some(&self.transaction_id) {
+            locks.remove(&self.pubkey);
+        }
+    }
+}
+
 /// Main accounts database with transaction support
 pub struct AccountsDb {
-    accounts: Arc<RwLock<HashMap<[u8; 32], AccountState>>>,
+    accounts: Arc<RwLock<HashMap<Pubkey, AccountState>>>,
     pub transactions: Arc<RwLock<HashMap<TransactionId, Transaction>>>,
-    pub account_locks: Arc<RwLock<HashMap<[u8; 32], TransactionId>>>, // Maps account to locking transaction
+    pub account_locks: Arc<RwLock<HashMap<Pubkey, TransactionId>>>, // Maps account to locking transaction
     next_transaction_id: Arc<RwLock<TransactionId>>,
 }
 
+impl Default for AccountsDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AccountsDb {
     pub fn new() -> Self {
         Self {
@@ -184,16 +479,16 @@ impl AccountsDb {
     }
 
     /// Load an account for write access with pessimistic locking
-    pub fn load_account_for_write(&self, pubkey: &[u8; 32], tx: &Transaction) 
+    pub fn load_account_for_write(&self, pubkey: &Pubkey, tx: &Transaction) 
         -> Result<AccountWriteGuard, AccountError> {
         
         // Check if account is already locked by another transaction
         {
             let locks = self.account_locks.read().unwrap();
-            if let Some(&locking_tx_id) = locks.get(pubkey) {
-                if locking_tx_id != tx.id {
-                    return Err(AccountError::AccountLocked);
-                }
+            if let Some(&locking_tx_id) = locks.get(pubkey)
+                && locking_tx_id != tx.id
+            {
+                return Err(AccountError::AccountLocked);
             }
         }
 
@@ -207,7 +502,7 @@ impl AccountsDb {
         let account = {
             let accounts = self.accounts.read().unwrap();
             accounts.get(pubkey).cloned()
-                .unwrap_or_else(|| AccountState::new(0, Vec::new(), [0; 32]))
+                .unwrap_or_else(|| AccountState::new(0, Vec::new(), Pubkey::default()))
         };
 
         // Store original state for rollback if not already stored
@@ -300,6 +595,95 @@ impl AccountsDb {
         Ok(())
     }
 
+    /// Load an account for write access like `load_account_for_write`, with
+    /// the working copy of its data in `arena` instead of a cloned `Vec`.
+    ///
+    /// Fails with `AlreadyLoaded` if `tx` already holds the account's lock,
+    /// through a live scratch guard or a live `AccountWriteGuard`. Two guards
+    /// for one account would each start from the stored state, and
+    /// `commit_scratch` would keep only the last one's changes.
+    pub fn load_account_for_write_in<'a>(&'a self, pubkey: &Pubkey, tx: &Transaction, arena: &'a Arena)
+        -> Result<ScratchWriteGuard<'a>, AccountError> {
+
+        // Check and take the lock under one write lock
+        {
+            let mut locks = self.account_locks.write().unwrap();
+            match locks.get(pubkey) {
+                Some(&locking_tx_id) if locking_tx_id != tx.id => return Err(AccountError::AccountLocked),
+                Some(_) => return Err(AccountError::AlreadyLoaded),
+                None => {
+                    locks.insert(*pubkey, tx.id);
+                }
+            }
+        }
+
+        let accounts = self.accounts.read().unwrap();
+        let (lamports, owner, data) = match accounts.get(pubkey) {
+            Some(account) => (account.lamports, account.owner, arena.alloc_slice_copy(&account.data)),
+            None => (0, Pubkey::default(), &mut [][..]),
+        };
+
+        Ok(ScratchWriteGuard {
+            pubkey: *pubkey,
+            lamports,
+            owner,
+            data,
+            arena,
+            transaction_id: tx.id,
+            accounts_db: self,
+        })
+    }
+
+    /// Commit a transaction whose accounts were loaded with
+    /// `load_account_for_write_in`. Each account's data is copied into the
+    /// stored account's buffer, which only allocates if the data grew. The
+    /// locks are released when the guards drop.
+    ///
+    /// Fails with `InvalidTransaction`, leaving the transaction active, if
+    /// any guard belongs to another transaction or another database, or if
+    /// the transaction also loaded accounts with `load_account_for_write`.
+    /// Those changes are only applied by `commit_transaction`, so committing
+    /// here would silently drop them.
+    pub fn commit_scratch(&self, tx: Transaction, guards: &[ScratchWriteGuard<'_>]) -> Result<(), AccountError> {
+        let mut transactions = self.transactions.write().unwrap();
+        let mut accounts = self.accounts.write().unwrap();
+
+        // Verify transaction is still active, has no regular modifications
+        // and owns every guard
+        match transactions.get(&tx.id) {
+            Some(stored_tx) if stored_tx.status != TransactionStatus::Active => {
+                return Err(AccountError::InvalidTransaction);
+            }
+            Some(stored_tx) if !stored_tx.modifications.is_empty() => {
+                return Err(AccountError::InvalidTransaction);
+            }
+            Some(_) => {}
+            None => return Err(AccountError::TransactionNotFound),
+        }
+        if guards
+            .iter()
+            .any(|guard| guard.transaction_id != tx.id || !std::ptr::eq(guard.accounts_db, self))
+        {
+            return Err(AccountError::InvalidTransaction);
+        }
+
+        for guard in guards {
+            let account = accounts
+                .entry(guard.pubkey)
+                .or_insert_with(|| AccountState::new(0, Vec::new(), Pubkey::default()));
+            account.lamports = guard.lamports;
+            account.owner = guard.owner;
+            account.data.clear();
+            account.data.extend_from_slice(guard.data);
+        }
+
+        if let Some(transaction) = transactions.get_mut(&tx.id) {
+            transaction.status = TransactionStatus::Committed;
+        }
+
+        Ok(())
+    }
+
     /// Get a transaction by ID
     pub fn get_transaction(&self, tx_id: TransactionId) -> Result<Transaction, AccountError> {
         let transactions = self.transactions.read().unwrap();
@@ -309,16 +693,87 @@ impl AccountsDb {
     }
 
     /// Get account state (read-only)
-    pub fn get_account(&self, pubkey: &[u8; 32]) -> Option<AccountState> {
+    pub fn get_account(&self, pubkey: &Pubkey) -> Option<AccountState> {
+        self.with_account(pubkey, AccountState::clone)
+    }
+
+    /// Read an account in place under the read lock. Unlike `get_account`,
+    /// nothing is cloned, so reads do not allocate.
+    pub fn with_account<R>(&self, pubkey: &Pubkey, f: impl FnOnce(&AccountState) -> R) -> Option<R> {
         let accounts = self.accounts.read().unwrap();
-        accounts.get(pubkey).cloned()
+        accounts.get(pubkey).map(f)
     }
 
     /// Create a new account
-    pub fn create_account(&self, pubkey: [u8; 32], account: AccountState) {
+    pub fn create_account(&self, pubkey: Pubkey, account: AccountState) {
         let mut accounts = self.accounts.write().unwrap();
         accounts.insert(pubkey, account);
     }
+
+    /// Dump every account as back-to-back zero-copy records, sorted by
+    /// pubkey. `load_accounts` reads the result back. Fails if an account
+    /// cannot be encoded, such as one with more than `u32::MAX` bytes of
+    /// data, with the error's offset relative to the start of the dump.
+    pub fn dump_accounts(&self) -> Result<Vec<u8>, ParseError> {
+        let accounts = self.accounts.read().unwrap();
+        let mut entries: Vec<_> = accounts.iter().collect();
+        entries.sort_by_key(|(pubkey, _)| **pubkey);
+
+        let total = entries
+            .iter()
+            .map(|(_, account)| account.encoded_len().next_multiple_of(RECORD_ALIGNMENT))
+            .sum();
+        let mut buf = vec![0u8; total];
+        let mut offset = 0;
+        for (pubkey, account) in entries {
+            let written = account.encode(pubkey, &mut buf[offset..]).map_err(|e| e.offset_by(offset))?;
+            offset += written.next_multiple_of(RECORD_ALIGNMENT);
+        }
+        Ok(buf)
+    }
+
+    /// Load accounts written by `dump_accounts`, replacing existing
+    /// accounts with the same pubkey. Nothing is loaded if any record is
+    /// invalid. Returns the number of accounts loaded.
+    pub fn load_accounts(&self, bytes: &[u8]) -> Result<usize, ParseError> {
+        let mut loaded = Vec::new();
+        for record in AccountIter::new(bytes) {
+            let (offset, account) = record?;
+            let record = account.parse_as::<AccountStateRecord>().map_err(|e| e.offset_by(offset))?;
+            loaded.push((record.header.pubkey, AccountState::from_record(&account, &record)));
+        }
+
+        let count = loaded.len();
+        let mut accounts = self.accounts.write().unwrap();
+        accounts.extend(loaded);
+        Ok(count)
+    }
+
+    /// Serialize the given accounts as a program would receive them.
+    /// Each entry is `(pubkey, is_signer, is_writable)`.
+    pub fn program_input(
+        &self,
+        metas: &[(Pubkey, bool, bool)],
+        instruction_data: &[u8],
+        program_id: &Pubkey,
+    ) -> Result<Vec<u8>, AccountError> {
+        let accounts = self.accounts.read().unwrap();
+        let instruction_accounts = metas
+            .iter()
+            .map(|(pubkey, is_signer, is_writable)| {
+                let account = accounts.get(pubkey).ok_or(AccountError::AccountNotFound)?;
+                Ok(InstructionAccount {
+                    pubkey: *pubkey,
+                    account,
+                    is_signer: *is_signer,
+                    is_writable: *is_writable,
+                })
+            })
+            .collect::<Result<Vec<_>, AccountError>>()?;
+
+        serialize_program_input(&instruction_accounts, instruction_data, program_id)
+            .map_err(|_| AccountError::InvalidAccountData)
+    }
 }
 
 pub fn run_account_state_management() {
@@ -327,13 +782,13 @@ pub fn run_account_state_management() {
     let db = AccountsDb::new();
     
     // Create some test accounts
-    let alice_pubkey = [1u8; 32];
-    let bob_pubkey = [2u8; 32];
-    let charlie_pubkey = [3u8; 32];
+    let alice_pubkey = Pubkey::new([1u8; 32]);
+    let bob_pubkey = Pubkey::new([2u8; 32]);
+    let charlie_pubkey = Pubkey::new([3u8; 32]);
     
-    let alice_account = AccountState::new(1000, b"Alice's data".to_vec(), [0u8; 32]);
-    let bob_account = AccountState::new(500, b"Bob's data".to_vec(), [0u8; 32]);
-    let charlie_account = AccountState::new(200, b"Charlie's data".to_vec(), [0u8; 32]);
+    let alice_account = AccountState::new(1000, b"Alice's data".to_vec(), Pubkey::default());
+    let bob_account = AccountState::new(500, b"Bob's data".to_vec(), Pubkey::default());
+    let charlie_account = AccountState::new(200, b"Charlie's data".to_vec(), Pubkey::default());
     
     db.create_account(alice_pubkey, alice_account);
     db.create_account(bob_pubkey, bob_account);
@@ -421,6 +876,411 @@ pub fn run_account_state_management() {
     
     db.rollback_transaction(tx3).unwrap();
     db.rollback_transaction(tx4).unwrap();
+
+    // Working copies in a per-transaction arena instead of cloned accounts
+    println!("\n=== Arena Scratch Copies ===");
+    let mut arena = Arena::new();
+    let tx5 = db.begin_transaction(5, 104);
+    let mut alice_guard = db.load_account_for_write_in(&alice_pubkey, &tx5, &arena).unwrap();
+    let mut bob_guard = db.load_account_for_write_in(&bob_pubkey, &tx5, &arena).unwrap();
+    let alice_before = alice_guard.get_lamports();
+    alice_guard.transfer_lamports(100).unwrap();
+    bob_guard.add_lamports(100);
+    alice_guard.get_data_mut()[0] = b'a';
+    println!("Copied Alice and Bob into {} bytes of arena", arena.allocated_bytes());
+    match db.commit_scratch(tx5, &[alice_guard, bob_guard]) {
+        Ok(()) if db.get_account(&alice_pubkey).unwrap().lamports == alice_before - 100 => {
+            println!("✓ Committed the transfer from the arena copies")
+        }
+        Ok(()) => println!("Committed, but Alice's balance is wrong"),
+        Err(e) => println!("Failed to commit scratch transaction: {}", e),
+    }
+
+    arena.reset();
+    let tx6 = db.begin_transaction(6, 105);
+    let charlie_before = db.get_account(&charlie_pubkey);
+    let mut charlie_guard = db.load_account_for_write_in(&charlie_pubkey, &tx6, &arena).unwrap();
+    charlie_guard.transfer_lamports(50).unwrap();
+    drop(charlie_guard);
+    db.rollback_transaction(tx6).unwrap();
+    if db.get_account(&charlie_pubkey) == charlie_before {
+        println!("✓ Dropping an uncommitted guard left Charlie unchanged");
+    } else {
+        println!("Charlie changed without a commit");
+    }
     
+    // Serialize accounts the way a program sees them and read them back
+    println!("\n=== Program Input Round Trip ===");
+    let program_id = Pubkey::new([9u8; 32]);
+    let metas = [(alice_pubkey, true, true), (bob_pubkey, false, true), (alice_pubkey, true, true)];
+    let input_bytes = db.program_input(&metas, b"transfer", &program_id).unwrap();
+    println!("Serialized {} accounts into {} bytes", metas.len(), input_bytes.len());
+
+    match ProgramInput::from_bytes(&input_bytes) {
+        Ok(input) => {
+            for (index, entry) in input.accounts().enumerate() {
+                match entry {
+                    InputAccount::Account(view) => println!(
+                        "  [{}] {} signer={} writable={} lamports={}",
+                        index, view.key(), view.is_signer(), view.is_writable(), view.lamports()
+                    ),
+                    InputAccount::Duplicate(original) => println!("  [{}] duplicate of [{}]", index, original),
+                }
+            }
+            println!("  instruction data: {:?}", String::from_utf8_lossy(input.instruction_data()));
+
+            let alice_roundtrip = AccountState::from(input.account(0).unwrap());
+            if Some(alice_roundtrip) == db.get_account(&alice_pubkey) {
+                println!("✓ Alice's account survives the round trip unchanged");
+            } else {
+                println!("Alice's account changed during the round trip");
+            }
+        }
+        Err(e) => println!("Failed to parse program input: {}", e),
+    }
+
+    // Dump the accounts through the zero-copy format and reload them
+    println!("\n=== Dump and Reload ===");
+    let program_account = AccountState {
+        executable: true,
+        rent_epoch: 361,
+        ..AccountState::new(1_141_440, b"program bytes".to_vec(), Pubkey::default())
+    };
+    db.create_account(program_id, program_account);
+    let dump = match db.dump_accounts() {
+        Ok(dump) => dump,
+        Err(e) => {
+            println!("Failed to dump accounts: {}", e);
+            return;
+        }
+    };
+    let restored = AccountsDb::new();
+    match restored.load_accounts(&dump) {
+        Ok(count) => println!("Reloaded {} accounts from a {} byte dump", count, dump.len()),
+        Err(e) => println!("Failed to reload accounts: {}", e),
+    }
+    for (name, pubkey) in [
+        ("Alice", alice_pubkey),
+        ("Bob", bob_pubkey),
+        ("Charlie", charlie_pubkey),
+        ("Program (executable, rent epoch 361)", program_id),
+    ] {
+        if restored.get_account(&pubkey) == db.get_account(&pubkey) {
+            println!("✓ {} matches the original", name);
+        } else {
+            println!("{} differs from the original", name);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        use crate::zero_copy_deserialization::account_file::AccountFile;
+
+        println!("\n=== Memory-Mapped Account File ===");
+        let path = std::env::temp_dir().join(format!("sonic-test-accounts-{}.bin", std::process::id()));
+        std::fs::write(&path, &dump).unwrap();
+        // The file is private to this demo and only rewritten once the
+        // mapping is dropped
+        match unsafe { AccountFile::open(&path) } {
+            Ok(file) => {
+                println!("Mapped {} accounts from a {} byte file", file.num_accounts(), file.len());
+                let total: u64 = file.accounts().flatten().map(|(_, account)| account.amount()).sum();
+                println!("  Total lamports: {}", total);
+                let mapped = AccountsDb::new();
+                match mapped.load_accounts(file.as_bytes()) {
+                    Ok(_) if mapped.get_account(&program_id) == db.get_account(&program_id) => {
+                        println!("✓ Loaded the mapped file without reading it into a Vec")
+                    }
+                    Ok(_) => println!("Mapped program account differs from the original"),
+                    Err(e) => println!("Failed to load mapped accounts: {}", e),
+                }
+            }
+            Err(e) => println!("Failed to map account file: {}", e),
+        }
+
+        std::fs::write(&path, &dump[..dump.len() - 10]).unwrap();
+        match unsafe { AccountFile::open(&path) } {
+            Ok(_) => println!("Unexpected success with truncated account file"),
+            Err(e) => println!("✓ Correctly rejected truncated account file: {}", e),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
     println!("\nAccount state management demonstration completed!");
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> AccountState {
+        AccountState {
+            lamports: 1_000,
+            data: b"sample data".to_vec(),
+            owner: Pubkey::new([3; 32]),
+            executable: true,
+            rent_epoch: 42,
+        }
+    }
+
+    #[test]
+    fn encode_writes_a_record_account() {
+        let state = sample();
+        let pubkey = Pubkey::new([1; 32]);
+        let mut buf = vec![0u8; state.encoded_len() + 4];
+        assert_eq!(state.encode(&pubkey, &mut buf), Ok(state.encoded_len()));
+
+        let account = Account::from_bytes(&buf).unwrap();
+        assert_eq!(account.discriminator(), ACCOUNT_STATE_DISCRIMINATOR);
+        assert_eq!((account.amount(), *account.owner()), (1_000, state.owner));
+        assert_eq!(account.total_len(), state.encoded_len());
+
+        let record = account.parse_as::<AccountStateRecord>().unwrap();
+        assert_eq!(record.header.version, ACCOUNT_STATE_LAYOUT_VERSION);
+        assert_eq!(record.header.pubkey, pubkey);
+        assert_eq!(record.data, b"sample data");
+        assert_eq!(buf[state.encoded_len()..], [0; 4]);
+    }
+
+    #[test]
+    fn encode_reports_a_short_buffer() {
+        let state = sample();
+        let mut buf = vec![0u8; state.encoded_len() - 1];
+        assert_eq!(
+            state.encode(&Pubkey::default(), &mut buf),
+            Err(ParseError::CapacityExceeded {
+                field: "account",
+                offset: 0,
+                required: state.encoded_len(),
+                capacity: buf.len(),
+            })
+        );
+    }
+
+    #[test]
+    fn try_from_round_trips_encode() {
+        for state in [sample(), AccountState::new(0, Vec::new(), Pubkey::default())] {
+            let mut buf = vec![0u8; state.encoded_len()];
+            state.encode(&Pubkey::new([1; 32]), &mut buf).unwrap();
+            let account = Account::from_bytes(&buf).unwrap();
+            assert_eq!(AccountState::try_from(account), Ok(state));
+        }
+    }
+
+    #[test]
+    fn try_from_rejects_other_accounts() {
+        let mut buf = [0u8; 128];
+        AccountWriter::new().discriminator(1).amount(5).data(b"plain").write(&mut buf).unwrap();
+        let account = Account::from_bytes(&buf).unwrap();
+        assert_eq!(
+            AccountState::try_from(account),
+            Err(ParseError::DiscriminatorMismatch {
+                offset: 0,
+                expected: ACCOUNT_STATE_DISCRIMINATOR,
+                actual: 1,
+            })
+        );
+
+        // A record with an unknown layout version
+        let state = sample();
+        let mut buf = vec![0u8; state.encoded_len()];
+        state.encode(&Pubkey::default(), &mut buf).unwrap();
+        let header_len = LayoutVersion::CURRENT.header_len();
+        buf[header_len + AccountStateRecordHeader::VERSION_OFFSET] = 9;
+        assert_eq!(
+            AccountState::try_from(Account::from_bytes(&buf).unwrap()),
+            Err(ParseError::InvalidValue { field: "version", offset: header_len })
+        );
+    }
+
+    #[test]
+    fn dump_and_load_round_trip() {
+        let db = AccountsDb::new();
+        let accounts = [
+            (Pubkey::new([2; 32]), sample()),
+            (Pubkey::new([1; 32]), AccountState::new(7, b"odd".to_vec(), Pubkey::default())),
+            (Pubkey::new([3; 32]), AccountState::new(0, Vec::new(), Pubkey::new([9; 32]))),
+        ];
+        for (pubkey, state) in &accounts {
+            db.create_account(*pubkey, state.clone());
+        }
+
+        let dump = db.dump_accounts().unwrap();
+        // Records are sorted by pubkey and start on the record boundary
+        let offsets: Vec<usize> = AccountIter::new(&dump).map(|record| record.unwrap().0).collect();
+        assert_eq!(offsets.len(), 3);
+        assert!(offsets.iter().all(|offset| offset % RECORD_ALIGNMENT == 0));
+
+        let restored = AccountsDb::new();
+        assert_eq!(restored.load_accounts(&dump), Ok(3));
+        for (pubkey, state) in &accounts {
+            assert_eq!(restored.get_account(pubkey).as_ref(), Some(state));
+        }
+        assert_eq!(restored.dump_accounts().unwrap(), dump);
+    }
+
+    #[test]
+    fn load_rejects_a_bad_record_and_loads_nothing() {
+        let db = AccountsDb::new();
+        db.create_account(Pubkey::new([1; 32]), sample());
+        db.create_account(Pubkey::new([2; 32]), sample());
+        let mut dump = db.dump_accounts().unwrap();
+
+        // Corrupt the second record's discriminator
+        let second = AccountIter::new(&dump).nth(1).unwrap().unwrap().0;
+        dump[second] = 1;
+
+        let restored = AccountsDb::new();
+        assert_eq!(
+            restored.load_accounts(&dump),
+            Err(ParseError::DiscriminatorMismatch {
+                offset: second,
+                expected: ACCOUNT_STATE_DISCRIMINATOR,
+                actual: 1,
+            })
+        );
+        assert!(restored.get_account(&Pubkey::new([1; 32])).is_none());
+    }
+
+    #[test]
+    fn dump_reports_data_too_long_to_encode() {
+        let db = AccountsDb::new();
+        db.create_account(Pubkey::new([1; 32]), sample());
+        // Zeroed allocations are mapped lazily, so this does not touch 4 GiB
+        let huge = AccountState { data: vec![0; u32::MAX as usize + 1], ..sample() };
+        db.create_account(Pubkey::new([2; 32]), huge);
+
+        assert_eq!(
+            db.dump_accounts(),
+            Err(ParseError::CapacityExceeded {
+                field: "data_len",
+                offset: sample().encoded_len().next_multiple_of(RECORD_ALIGNMENT),
+                required: AccountStateRecordHeader::SIZE + u32::MAX as usize + 1,
+                capacity: u32::MAX as usize,
+            })
+        );
+    }
+
+    fn scratch_db() -> (AccountsDb, Pubkey, Pubkey) {
+        let db = AccountsDb::new();
+        let alice = Pubkey::new([1; 32]);
+        let bob = Pubkey::new([2; 32]);
+        db.create_account(alice, AccountState::new(1_000, b"alice".to_vec(), Pubkey::default()));
+        db.create_account(bob, AccountState::new(500, b"bob".to_vec(), Pubkey::default()));
+        (db, alice, bob)
+    }
+
+    #[test]
+    fn commit_scratch_applies_every_guard() {
+        let (db, alice, bob) = scratch_db();
+        let arena = Arena::new();
+        let tx = db.begin_transaction(1, 1);
+        let tx_id = tx.id;
+
+        let mut alice_guard = db.load_account_for_write_in(&alice, &tx, &arena).unwrap();
+        let mut bob_guard = db.load_account_for_write_in(&bob, &tx, &arena).unwrap();
+        alice_guard.transfer_lamports(300).unwrap();
+        bob_guard.add_lamports(300);
+        alice_guard.get_data_mut()[0] = b'A';
+        bob_guard.set_data(b"bob, longer");
+        bob_guard.set_owner(Pubkey::new([7; 32]));
+
+        // Nothing is visible before the commit
+        assert_eq!(db.get_account(&alice).unwrap().lamports, 1_000);
+        assert_eq!(db.commit_scratch(tx, &[alice_guard, bob_guard]), Ok(()));
+
+        let alice_state = db.get_account(&alice).unwrap();
+        assert_eq!((alice_state.lamports, &alice_state.data[..]), (700, &b"Alice"[..]));
+        let bob_state = db.get_account(&bob).unwrap();
+        assert_eq!((bob_state.lamports, &bob_state.data[..]), (800, &b"bob, longer"[..]));
+        assert_eq!(bob_state.owner, Pubkey::new([7; 32]));
+        assert_eq!(db.get_transaction(tx_id).unwrap().status, TransactionStatus::Committed);
+        assert!(db.account_locks.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dropping_scratch_guards_discards_changes() {
+        let (db, alice, _) = scratch_db();
+        let arena = Arena::new();
+        let tx = db.begin_transaction(1, 1);
+
+        let mut guard = db.load_account_for_write_in(&alice, &tx, &arena).unwrap();
+        guard.set_lamports(0);
+        guard.set_data(b"gone");
+        assert!(matches!(
+            db.load_account_for_write_in(&alice, &db.begin_transaction(1, 2), &arena),
+            Err(AccountError::AccountLocked)
+        ));
+        drop(guard);
+
+        assert_eq!(db.get_account(&alice), Some(AccountState::new(1_000, b"alice".to_vec(), Pubkey::default())));
+        assert!(db.account_locks.read().unwrap().is_empty());
+        assert!(db.load_account_for_write_in(&alice, &db.begin_transaction(1, 3), &arena).is_ok());
+    }
+
+    #[test]
+    fn commit_scratch_rejects_regular_modifications() {
+        let (db, alice, bob) = scratch_db();
+        let arena = Arena::new();
+        let tx = db.begin_transaction(1, 1);
+        let tx_id = tx.id;
+
+        let mut regular = db.load_account_for_write(&bob, &tx).unwrap();
+        regular.add_lamports(1);
+        drop(regular);
+        let mut scratch = db.load_account_for_write_in(&alice, &tx, &arena).unwrap();
+        scratch.set_lamports(1);
+
+        assert_eq!(db.commit_scratch(tx.clone(), &[scratch]), Err(AccountError::InvalidTransaction));
+        assert_eq!(db.get_account(&alice).unwrap().lamports, 1_000);
+        assert_eq!(db.get_account(&bob).unwrap().lamports, 500);
+        // The transaction is still active and can be rolled back
+        assert_eq!(db.get_transaction(tx_id).unwrap().status, TransactionStatus::Active);
+        assert_eq!(db.rollback_transaction(tx), Ok(()));
+    }
+
+    #[test]
+    fn commit_scratch_rejects_foreign_guards() {
+        let (db, alice, bob) = scratch_db();
+        let (other_db, _, _) = scratch_db();
+        let arena = Arena::new();
+
+        // A guard from another transaction
+        let tx = db.begin_transaction(1, 1);
+        let other_tx = db.begin_transaction(1, 2);
+        let guard = db.load_account_for_write_in(&alice, &tx, &arena).unwrap();
+        let other = db.load_account_for_write_in(&bob, &other_tx, &arena).unwrap();
+        assert_eq!(db.commit_scratch(tx.clone(), &[guard, other]), Err(AccountError::InvalidTransaction));
+
+        // A guard from another database with the same transaction id
+        let foreign_tx = other_db.begin_transaction(1, 1);
+        assert_eq!(foreign_tx.id, tx.id);
+        let mut foreign = other_db.load_account_for_write_in(&alice, &foreign_tx, &arena).unwrap();
+        foreign.set_lamports(0);
+        assert_eq!(db.commit_scratch(tx, &[foreign]), Err(AccountError::InvalidTransaction));
+        assert_eq!(db.get_account(&alice).unwrap().lamports, 1_000);
+        assert_eq!(other_db.get_account(&alice).unwrap().lamports, 1_000);
+    }
+
+    #[test]
+    fn second_scratch_guard_for_an_account_is_rejected() {
+        let (db, alice, bob) = scratch_db();
+        let arena = Arena::new();
+        let tx = db.begin_transaction(1, 1);
+
+        let mut first = db.load_account_for_write_in(&alice, &tx, &arena).unwrap();
+        first.transfer_lamports(300).unwrap();
+        assert!(matches!(db.load_account_for_write_in(&alice, &tx, &arena), Err(AccountError::AlreadyLoaded)));
+        // The failed load leaves the first guard's lock in place
+        assert_eq!(db.account_locks.read().unwrap().get(&alice), Some(&tx.id));
+
+        // Nor while a regular guard of the transaction holds it
+        let regular = db.load_account_for_write(&bob, &tx).unwrap();
+        assert!(matches!(db.load_account_for_write_in(&bob, &tx, &arena), Err(AccountError::AlreadyLoaded)));
+        drop(regular);
+
+        // Once the first guard is dropped the account can be loaded again
+        drop(first);
+        let guard = db.load_account_for_write_in(&alice, &tx, &arena).unwrap();
+        assert_eq!(guard.get_lamports(), 1_000);
+    }
+}
@@ -0,0 +1,88 @@
+// Fixed-endian integer wrappers for on-disk and on-wire layouts.
+//
+// Each type stores its value as a byte array in a declared byte order, so it
+// has alignment 1, reads the same on every host, and can be used as a field
+// of a `#[derive(ZeroCopy)]` struct.
+
+use core::fmt;
+
+use crate::zero_copy_deserialization::Pod;
+
+macro_rules! endian_int {
+    ($(#[$meta:meta])* $name:ident, $int:ty, $from:ident, $to:ident) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+        pub struct $name([u8; core::mem::size_of::<$int>()]);
+
+        impl $name {
+            pub const fn new(value: $int) -> Self {
+                $name(value.$to())
+            }
+
+            pub const fn get(self) -> $int {
+                <$int>::$from(self.0)
+            }
+
+            pub fn set(&mut self, value: $int) {
+                self.0 = value.$to();
+            }
+
+            /// The stored bytes, in this type's byte order.
+            pub const fn to_bytes(self) -> [u8; core::mem::size_of::<$int>()] {
+                self.0
+            }
+
+            pub const fn from_bytes(bytes: [u8; core::mem::size_of::<$int>()]) -> Self {
+                $name(bytes)
+            }
+        }
+
+        impl From<$int> for $name {
+            fn from(value: $int) -> Self {
+                $name::new(value)
+            }
+        }
+
+        impl From<$name> for $int {
+            fn from(value: $name) -> Self {
+                value.get()
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(&self.get(), f)
+            }
+        }
+
+        // A byte array of the integer's width: every pattern is valid and
+        // there is no padding
+        unsafe impl Pod for $name {}
+    };
+}
+
+endian_int!(
+    /// Little-endian `u16`.
+    LeU16, u16, from_le_bytes, to_le_bytes
+);
+endian_int!(
+    /// Little-endian `u32`.
+    LeU32, u32, from_le_bytes, to_le_bytes
+);
+endian_int!(
+    /// Little-endian `u64`.
+    LeU64, u64, from_le_bytes, to_le_bytes
+);
+endian_int!(
+    /// Big-endian `u16`.
+    BeU16, u16, from_be_bytes, to_be_bytes
+);
+endian_int!(
+    /// Big-endian `u32`.
+    BeU32, u32, from_be_bytes, to_be_bytes
+);
+endian_int!(
+    /// Big-endian `u64`.
+    BeU64, u64, from_be_bytes, to_be_bytes
+);
@@ -1,4 +1,18 @@
+// The zero-copy parser, `Pubkey` and the endian wrappers only use `core`, so
+// they build without `std` or an allocator for on-chain use. Everything else
+// needs the `std` feature, which is on by default.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Lets `#[derive(ZeroCopy)]` output, which names `::sonic_test`, compile
+// inside this crate too.
+extern crate self as sonic_test;
+
+pub mod endian;
+pub mod pubkey;
 pub mod zero_copy_deserialization;
+#[cfg(feature = "std")]
 pub mod unsafe_rust_memory_management;
+#[cfg(feature = "std")]
 pub mod turbine_block_propagation;
-pub mod account_state_management;
\ No newline at end of file
+#[cfg(feature = "std")]
+pub mod account_state_management;
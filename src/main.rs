@@ -1,16 +1,15 @@
-pub mod zero_copy_deserialization;
-pub mod unsafe_rust_memory_management;
-pub mod turbine_block_propagation;
-pub mod account_state_management;
-
-use zero_copy_deserialization::run_zero_copy_deserialization;
-use account_state_management::run_account_state_management;
+use sonic_test::turbine_block_propagation;
+use sonic_test::unsafe_rust_memory_management;
+use sonic_test::zero_copy_deserialization::run_zero_copy_deserialization;
+use sonic_test::account_state_management::run_account_state_management;
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     
     if args.len() > 1 && args[1] == "1_1" {
         run_zero_copy_deserialization();
+    } else if args.len() > 1 && args[1] == "1_2" {
+        unsafe_rust_memory_management::main();
     } else if args.len() > 1 && args[1] == "3_2" {
         run_account_state_management();
     } else if args.len() > 1 && args[1] == "2_2" {
@@ -19,8 +18,9 @@ fn main() {
     }
     
     else {
-        println!("Usage: cargo run [1_1|2_2|3_2]");
+        println!("Usage: cargo run [1_1|1_2|2_2|3_2]");
         println!("1_1: zero-copy deserialization example");
+        println!("1_2: unsafe Rust and memory management example");
         println!("2_2: turbine block propagation example");
         println!("3_2: account state management example");
     }
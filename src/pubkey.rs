@@ -0,0 +1,257 @@
+// 32-byte account address, displayed and parsed as base58 like Solana
+// addresses.
+//
+// `Pubkey` is `repr(transparent)` over `[u8; 32]`, so it can be used directly
+// as a field of zero-copy layouts.
+
+use core::fmt;
+use core::str::FromStr;
+
+use crate::zero_copy_deserialization::Pod;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Longest base58 encoding of 32 bytes.
+pub const MAX_BASE58_LEN: usize = 44;
+
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pubkey([u8; 32]);
+
+// A byte array: every pattern is valid and there is no padding
+unsafe impl Pod for Pubkey {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ParsePubkeyError {
+    #[error("invalid base58 character {character:?} at position {position}")]
+    InvalidCharacter { character: char, position: usize },
+    #[error("base58 string does not decode to 32 bytes")]
+    InvalidLength,
+}
+
+impl Pubkey {
+    pub const fn new(bytes: [u8; 32]) -> Self {
+        Pubkey(bytes)
+    }
+
+    pub const fn to_bytes(self) -> [u8; 32] {
+        self.0
+    }
+
+    pub const fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Views 32 bytes in place as a `Pubkey`.
+    pub fn from_bytes_ref(bytes: &[u8; 32]) -> &Pubkey {
+        // `Pubkey` is `repr(transparent)` over `[u8; 32]`
+        unsafe { &*(bytes as *const [u8; 32] as *const Pubkey) }
+    }
+
+    /// Writes the base58 encoding into `out` and returns it as a `&str`,
+    /// without allocating.
+    pub fn encode_base58<'a>(&self, out: &'a mut [u8; MAX_BASE58_LEN]) -> &'a str {
+        // Base-58 digits, least significant first
+        let mut digits = [0u8; MAX_BASE58_LEN];
+        let mut len = 0;
+        for &byte in &self.0 {
+            let mut carry = byte as u32;
+            for digit in &mut digits[..len] {
+                carry += (*digit as u32) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            }
+            while carry > 0 {
+                digits[len] = (carry % 58) as u8;
+                len += 1;
+                carry /= 58;
+            }
+        }
+
+        // Each leading zero byte is written as a leading '1'
+        let zeros = self.0.iter().take_while(|&&b| b == 0).count();
+        out[..zeros].fill(ALPHABET[0]);
+        for (slot, &digit) in out[zeros..zeros + len].iter_mut().zip(digits[..len].iter().rev()) {
+            *slot = ALPHABET[digit as usize];
+        }
+
+        core::str::from_utf8(&out[..zeros + len]).expect("base58 alphabet is ASCII")
+    }
+}
+
+impl FromStr for Pubkey {
+    type Err = ParsePubkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > MAX_BASE58_LEN {
+            return Err(ParsePubkeyError::InvalidLength);
+        }
+
+        // Base-256 digits, least significant first
+        let mut bytes = [0u8; 32];
+        let mut len = 0;
+        for (position, character) in s.char_indices() {
+            let value = ALPHABET
+                .iter()
+                .position(|&c| c as char == character)
+                .ok_or(ParsePubkeyError::InvalidCharacter { character, position })?;
+
+            let mut carry = value as u32;
+            for byte in &mut bytes[..len] {
+                carry += (*byte as u32) * 58;
+                *byte = carry as u8;
+                carry >>= 8;
+            }
+            while carry > 0 {
+                if len == bytes.len() {
+                    return Err(ParsePubkeyError::InvalidLength);
+                }
+                bytes[len] = carry as u8;
+                len += 1;
+                carry >>= 8;
+            }
+        }
+
+        // Each leading '1' is a leading zero byte
+        let zeros = s.bytes().take_while(|&c| c == ALPHABET[0]).count();
+        if zeros + len != bytes.len() {
+            return Err(ParsePubkeyError::InvalidLength);
+        }
+
+        bytes[..len].reverse();
+        bytes.copy_within(..len, zeros);
+        bytes[..zeros].fill(0);
+        Ok(Pubkey(bytes))
+    }
+}
+
+impl fmt::Display for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.encode_base58(&mut [0u8; MAX_BASE58_LEN]))
+    }
+}
+
+impl fmt::Debug for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl From<[u8; 32]> for Pubkey {
+    fn from(bytes: [u8; 32]) -> Self {
+        Pubkey(bytes)
+    }
+}
+
+impl From<Pubkey> for [u8; 32] {
+    fn from(pubkey: Pubkey) -> Self {
+        pubkey.0
+    }
+}
+
+impl AsRef<[u8]> for Pubkey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SPL Token program id
+    const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const TOKEN_PROGRAM_BYTES: [u8; 32] = [
+        0x06, 0xdd, 0xf6, 0xe1, 0xd7, 0x65, 0xa1, 0x93, 0xd9, 0xcb, 0xe1, 0x46, 0xce, 0xeb, 0x79, 0xac,
+        0x1c, 0xb4, 0x85, 0xed, 0x5f, 0x5b, 0x37, 0x91, 0x3a, 0x8c, 0xf5, 0x85, 0x7e, 0xff, 0x00, 0xa9,
+    ];
+
+    fn encode(pubkey: &Pubkey) -> String {
+        pubkey.encode_base58(&mut [0u8; MAX_BASE58_LEN]).to_owned()
+    }
+
+    #[test]
+    fn known_vectors() {
+        let vectors = [
+            (Pubkey::default(), "11111111111111111111111111111111"),
+            (Pubkey::new(TOKEN_PROGRAM_BYTES), TOKEN_PROGRAM),
+            (
+                Pubkey::new([
+                    0x06, 0xdd, 0xf6, 0xe1, 0xd7, 0x65, 0xa1, 0x93, 0xd9, 0xcb, 0xe1, 0x46, 0xce, 0xeb, 0x79, 0xac,
+                    0x20, 0xe5, 0x01, 0xa7, 0x3f, 0xbc, 0x76, 0xc4, 0x59, 0x64, 0x67, 0x6d, 0xbd, 0x3c, 0x68, 0xba,
+                ]),
+                "TokenkegQfeZyiNwAJbNbGqPXYHWTvDQ8mb5h8rR9Bf",
+            ),
+            (Pubkey::new([0xff; 32]), "JEKNVnkbo3jma5nREBBJCDoXFVeKkD56V3xKrvRmWxFG"),
+        ];
+        for (pubkey, text) in vectors {
+            assert_eq!(encode(&pubkey), text);
+            assert_eq!(pubkey.to_string(), text);
+            assert_eq!(text.parse::<Pubkey>(), Ok(pubkey));
+        }
+    }
+
+    #[test]
+    fn round_trips_pseudo_random_keys() {
+        // xorshift64, seeded so failures reproduce
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for i in 0..1_000 {
+            let mut bytes = [0u8; 32];
+            for chunk in bytes.chunks_mut(8) {
+                chunk.copy_from_slice(&next().to_le_bytes());
+            }
+            // Cover leading zero bytes, which encode as leading '1's
+            bytes[..i % 4].fill(0);
+
+            let pubkey = Pubkey::new(bytes);
+            let text = encode(&pubkey);
+            assert!(text.len() <= MAX_BASE58_LEN);
+            assert_eq!(text.parse::<Pubkey>(), Ok(pubkey), "{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_characters_outside_the_alphabet() {
+        for character in ['0', 'O', 'I', 'l'] {
+            let mut text = TOKEN_PROGRAM.to_owned();
+            text.replace_range(5..6, &character.to_string());
+            assert_eq!(
+                text.parse::<Pubkey>(),
+                Err(ParsePubkeyError::InvalidCharacter { character, position: 5 })
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_more_than_32_bytes() {
+        // 44 digits of 'z' exceed 2^256
+        assert_eq!("z".repeat(MAX_BASE58_LEN).parse::<Pubkey>(), Err(ParsePubkeyError::InvalidLength));
+        // One past the all-ones key
+        assert_eq!(
+            "JEKNVnkbo3jma5nREBBJCDoXFVeKkD56V3xKrvRmWxFH".parse::<Pubkey>(),
+            Err(ParsePubkeyError::InvalidLength)
+        );
+        assert_eq!("1".repeat(MAX_BASE58_LEN + 1).parse::<Pubkey>(), Err(ParsePubkeyError::InvalidLength));
+    }
+
+    #[test]
+    fn rejects_fewer_than_32_bytes() {
+        assert_eq!("".parse::<Pubkey>(), Err(ParsePubkeyError::InvalidLength));
+        assert_eq!("2".parse::<Pubkey>(), Err(ParsePubkeyError::InvalidLength));
+        assert_eq!(TOKEN_PROGRAM[1..].parse::<Pubkey>(), Err(ParsePubkeyError::InvalidLength));
+        assert_eq!("1".repeat(31).parse::<Pubkey>(), Err(ParsePubkeyError::InvalidLength));
+    }
+
+    #[test]
+    fn rejects_extra_leading_ones() {
+        assert_eq!("1".repeat(33).parse::<Pubkey>(), Err(ParsePubkeyError::InvalidLength));
+        assert_eq!(format!("1{TOKEN_PROGRAM}").parse::<Pubkey>(), Err(ParsePubkeyError::InvalidLength));
+    }
+}
@@ -1,6 +1,8 @@
+use crate::pubkey::Pubkey;
+
 #[derive(Clone, Debug)]
 struct Node {
-    pubkey: [u8; 32],
+    pubkey: Pubkey,
     stake: u64,
 }
 
@@ -11,35 +13,63 @@ struct TurbineTree {
 
 impl TurbineTree {
     fn new(fanout: usize, nodes: Vec<Node>) -> Self {
+        assert!(fanout > 0, "fanout must be at least 1");
         Self { fanout, nodes }
     }
 
+    // The leader followed by every other node, highest stake first. Ties
+    // are broken by pubkey so every validator derives the same tree. This
+    // is the tree in breadth-first order: node `i` retransmits to
+    // `i * fanout + 1 ..= i * fanout + fanout`. It only depends on the
+    // leader, so compute it once per shred and pass it to the retransmit
+    // lookups below.
+    fn tree_order(&self, leader: &Node) -> Vec<Node> {
+        let mut others: Vec<Node> = self.nodes.iter().filter(|node| node.pubkey != leader.pubkey).cloned().collect();
+        others.sort_by_key(|node| (std::cmp::Reverse(node.stake), node.pubkey));
+
+        let mut order = Vec::with_capacity(others.len() + 1);
+        order.push(leader.clone());
+        order.extend(others);
+        order
+    }
+
     fn build_layer_matrix(&self, leader: &Node) -> Vec<Vec<Node>> {
-        // 1. Sorts nodes by stake weight
-        let mut sorted_nodes = self.nodes.clone();
-        sorted_nodes.sort_by(|a, b| b.stake.cmp(&a.stake));
+        // 1. Sorts nodes by stake weight, with the leader at the root
+        let mut remaining = self.tree_order(leader).into_iter();
 
-        // 2. Constructs layers with the given fanout
+        // 2. Layer k holds fanout^k nodes, so every node in a layer has
+        // `fanout` children in the next one (the last layer may be partial)
         let mut layers: Vec<Vec<Node>> = Vec::new();
-        for i in 0..sorted_nodes.len() {
-            let layer: Vec<Node> = sorted_nodes[i..].iter().take(self.fanout).cloned().collect();
-            layers.push(layer);
+        let mut layer_len = 1;
+        while remaining.len() > 0 {
+            layers.push(remaining.by_ref().take(layer_len).collect());
+            layer_len = layer_len.saturating_mul(self.fanout);
         }
 
-        // 3. Ensures the leader is at the root
-        let leader_index = sorted_nodes.iter().position(|node| node.pubkey == leader.pubkey).unwrap();
-        let leader_layer = layers.remove(leader_index);
-        layers.insert(0, leader_layer);
-
-        // 4. Optimizes for network topology
-        let mut optimized_layers = Vec::new();
-        for layer in layers {
-            let mut layer_nodes = layer.clone();
-            layer_nodes.sort_by_key(|node| node.pubkey);
-            optimized_layers.push(layer_nodes);
-        }
+        layers
+    }
 
-        optimized_layers
+    // Position of `pubkey` in `tree_order`
+    fn tree_index(order: &[Node], pubkey: &Pubkey) -> Option<usize> {
+        order.iter().position(|node| node.pubkey == *pubkey)
+    }
+
+    /// Nodes that `node` forwards shreds to, given the `tree_order` for the
+    /// shred's leader, or `None` if `node` is not in the tree. Empty for the
+    /// last layer.
+    fn get_retransmit_children<'a>(&self, order: &'a [Node], node: &Pubkey) -> Option<&'a [Node]> {
+        let index = Self::tree_index(order, node)?;
+        let first = index.saturating_mul(self.fanout).saturating_add(1).min(order.len());
+        let last = first.saturating_add(self.fanout).min(order.len());
+        Some(&order[first..last])
+    }
+
+    /// Node that `node` receives shreds from, given the `tree_order` for the
+    /// shred's leader. `None` for the leader itself and for nodes not in the
+    /// tree.
+    fn get_retransmit_parent<'a>(&self, order: &'a [Node], node: &Pubkey) -> Option<&'a Node> {
+        let index = Self::tree_index(order, node)?.checked_sub(1)?;
+        Some(&order[index / self.fanout])
     }
 
     fn calculate_propagation_time(&self, layers: &[Vec<Node>]) -> u64 {
@@ -56,34 +86,20 @@ impl TurbineTree {
 
 // Example usage and main function
 pub fn main() {
-    // Create sample nodes
-    let nodes = vec![
-        Node {
-            pubkey: [1u8; 32],
-            stake: 1000,
-        },
-        Node {
-            pubkey: [2u8; 32],
-            stake: 2000,
-        },
-        Node {
-            pubkey: [3u8; 32],
-            stake: 1500,
-        },
-        Node {
-            pubkey: [4u8; 32],
-            stake: 500,
-        },
-    ];
-
-    // Create turbine tree with fanout of 2
-    let turbine_tree = TurbineTree::new(2, nodes);
+    // Create sample nodes. Stakes take five values, so most nodes share a
+    // stake with others and are ordered by pubkey
+    let nodes: Vec<Node> = (1..=13u8)
+        .map(|i| Node {
+            pubkey: Pubkey::new([i; 32]),
+            stake: 500 * (i as u64 % 5 + 1),
+        })
+        .collect();
+
+    // Create turbine tree with fanout of 3
+    let turbine_tree = TurbineTree::new(3, nodes.clone());
 
     // Define leader node
-    let leader = Node {
-        pubkey: [1u8; 32],
-        stake: 1000,
-    };
+    let leader = nodes[0].clone();
 
     // Build layer matrix
     let layers = turbine_tree.build_layer_matrix(&leader);
@@ -92,12 +108,155 @@ pub fn main() {
     for (i, layer) in layers.iter().enumerate() {
         println!("Layer {}: {} nodes", i, layer.len());
         for node in layer {
-            println!("  Node: {:?}, Stake: {}", 
+            println!("  Node: {}, Stake: {}", 
                      node.pubkey, node.stake);
         }
     }
 
+    // Each node asks who it forwards to and who it should hear from. The
+    // order is computed once for the leader and shared by every lookup
+    println!("Retransmit peers:");
+    let order = turbine_tree.tree_order(&leader);
+    for node in layers.iter().take(2).flatten() {
+        let children = turbine_tree.get_retransmit_children(&order, &node.pubkey).unwrap_or_default();
+        let parent = turbine_tree.get_retransmit_parent(&order, &node.pubkey);
+        println!(
+            "  {}: parent {}, {} children",
+            node.pubkey,
+            parent.map_or_else(|| "none (leader)".to_string(), |parent| parent.pubkey.to_string()),
+            children.len()
+        );
+    }
+
+    // Every non-leader node is a child of its parent
+    let consistent = nodes.iter().filter(|node| node.pubkey != leader.pubkey).all(|node| {
+        turbine_tree
+            .get_retransmit_parent(&order, &node.pubkey)
+            .and_then(|parent| turbine_tree.get_retransmit_children(&order, &parent.pubkey))
+            .is_some_and(|children| children.iter().any(|child| child.pubkey == node.pubkey))
+    });
+    println!("Parents and children agree for every node: {}", consistent);
+
     // Calculate total propagation time
     let total_time = turbine_tree.calculate_propagation_time(&layers);
     println!("Total propagation time: {}", total_time);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u8, stake: u64) -> Node {
+        Node { pubkey: Pubkey::new([id; 32]), stake }
+    }
+
+    // Nodes 1..=n with distinct stakes, highest first, so tree order is id order
+    fn nodes(n: u8) -> Vec<Node> {
+        (1..=n).map(|i| node(i, 1_000 - i as u64)).collect()
+    }
+
+    fn ids(nodes: &[Node]) -> Vec<u8> {
+        nodes.iter().map(|node| node.pubkey.as_bytes()[0]).collect()
+    }
+
+    #[test]
+    fn layers_grow_by_fanout() {
+        // 1 + 3 + 9 full layers and a partial fourth layer
+        let nodes = nodes(15);
+        let tree = TurbineTree::new(3, nodes.clone());
+        let layers = tree.build_layer_matrix(&nodes[0]);
+
+        let sizes: Vec<usize> = layers.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [1, 3, 9, 2]);
+        assert_eq!(ids(&layers[0]), [1]);
+        assert_eq!(ids(&layers[1]), [2, 3, 4]);
+        assert_eq!(ids(&layers[3]), [14, 15]);
+    }
+
+    #[test]
+    fn leader_is_the_root_whatever_its_stake() {
+        let nodes = nodes(5);
+        let leader = nodes[4].clone();
+        let tree = TurbineTree::new(2, nodes);
+
+        let layers = tree.build_layer_matrix(&leader);
+        assert_eq!(ids(&layers[0]), [5]);
+        assert_eq!(ids(&layers[1]), [1, 2]);
+        let order = tree.tree_order(&leader);
+        assert!(tree.get_retransmit_parent(&order, &leader.pubkey).is_none());
+        assert_eq!(ids(tree.get_retransmit_children(&order, &leader.pubkey).unwrap()), [1, 2]);
+    }
+
+    #[test]
+    fn every_node_is_a_child_of_its_parent() {
+        let nodes = nodes(40);
+        let leader = nodes[7].clone();
+        let tree = TurbineTree::new(3, nodes.clone());
+        let order = tree.tree_order(&leader);
+
+        for node in nodes.iter().filter(|node| node.pubkey != leader.pubkey) {
+            let parent = tree.get_retransmit_parent(&order, &node.pubkey).unwrap();
+            let children = tree.get_retransmit_children(&order, &parent.pubkey).unwrap();
+            assert!(children.iter().any(|child| child.pubkey == node.pubkey), "{}", node.pubkey);
+        }
+    }
+
+    #[test]
+    fn children_partition_the_tree() {
+        let nodes = nodes(40);
+        let tree = TurbineTree::new(4, nodes.clone());
+        let order = tree.tree_order(&nodes[0]);
+
+        let mut reached: Vec<u8> = nodes
+            .iter()
+            .flat_map(|node| ids(tree.get_retransmit_children(&order, &node.pubkey).unwrap()))
+            .collect();
+        reached.sort_unstable();
+        assert_eq!(reached, (2..=40).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn last_layer_has_no_children() {
+        let nodes = nodes(15);
+        let tree = TurbineTree::new(3, nodes.clone());
+        let layers = tree.build_layer_matrix(&nodes[0]);
+        let order = tree.tree_order(&nodes[0]);
+
+        // Node 5 is first in the third layer; its children would start at 14
+        assert_eq!(ids(tree.get_retransmit_children(&order, &nodes[4].pubkey).unwrap()), [14, 15]);
+        for node in layers[2].iter().skip(1).chain(&layers[3]) {
+            assert!(tree.get_retransmit_children(&order, &node.pubkey).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn equal_stakes_are_ordered_by_pubkey() {
+        let nodes = vec![node(9, 1), node(4, 50), node(3, 50), node(7, 100), node(5, 50)];
+        let tree = TurbineTree::new(2, nodes.clone());
+
+        let order = tree.tree_order(&nodes[0]);
+        assert_eq!(ids(&order), [9, 7, 3, 4, 5]);
+
+        // The input order does not matter
+        let mut reversed = nodes.clone();
+        reversed.reverse();
+        assert_eq!(ids(&TurbineTree::new(2, reversed).tree_order(&nodes[0])), [9, 7, 3, 4, 5]);
+    }
+
+    #[test]
+    fn unknown_node_has_no_peers() {
+        let nodes = nodes(5);
+        let tree = TurbineTree::new(2, nodes.clone());
+        let stranger = Pubkey::new([99; 32]);
+        let order = tree.tree_order(&nodes[0]);
+
+        assert!(tree.get_retransmit_children(&order, &stranger).is_none());
+        assert!(tree.get_retransmit_parent(&order, &stranger).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "fanout must be at least 1")]
+    fn zero_fanout_is_rejected() {
+        TurbineTree::new(0, nodes(3));
+    }
+}
@@ -1,24 +1,489 @@
-use std::sync::Arc;
+use std::alloc::{self, Layout, LayoutError};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut, Range};
+use std::ptr::{self, NonNull};
+use std::slice;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
 use std::thread;
 
-struct SharedBuffer {
+#[cfg(feature = "alloc-tracking")]
+pub mod alloc_tracking;
+pub mod arena;
+pub mod ring_buffer;
+#[cfg(target_os = "linux")]
+pub mod shared_memory;
+
+pub struct SharedBuffer {
     data: Arc<[u8]>,
 }
 
 impl SharedBuffer {
-    fn new(size: usize) -> Self {
+    pub fn new(size: usize) -> Self {
         // Initialize bytes (here: zeros). No UB.
         let slice = vec![0u8; size].into_boxed_slice();
         Self { data: Arc::from(slice) }
     }
 
-    fn get(&self, index: usize) -> Option<u8> {
+    pub fn get(&self, index: usize) -> Option<u8> {
         // Safe bounds-checked access, return by value (Copy)
         self.data.get(index).copied()
     }
 }
 
-fn main() {
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ReserveError {
+    #[error("range {start}..{end} is outside the buffer of {len} bytes")]
+    OutOfBounds { start: usize, end: usize, len: usize },
+    #[error("range {start}..{end} overlaps a reservation that is still held")]
+    Overlap { start: usize, end: usize },
+}
+
+/// Buffer that several threads can write at once, each through its own
+/// byte range.
+///
+/// Every access goes through a [`Reservation`], and `reserve` refuses a range
+/// that overlaps one still held, so no two threads can touch the same byte at
+/// the same time. The bookkeeping lock is held only while reserving and
+/// releasing, never while the bytes are used. Releasing and re-reserving goes
+/// through that lock, which orders writes made under one reservation before
+/// reads made under a later one.
+pub struct SharedBufferMut {
+    data: Box<[UnsafeCell<u8>]>,
+    reserved: Mutex<Vec<Range<usize>>>,
+}
+
+// Bytes are only reached through reservations, which are disjoint while held
+// (checked under `reserved`), so sharing the buffer cannot produce a data race
+unsafe impl Sync for SharedBufferMut {}
+
+impl SharedBufferMut {
+    pub fn new(size: usize) -> Self {
+        let data = (0..size).map(|_| UnsafeCell::new(0)).collect();
+        Self { data, reserved: Mutex::new(Vec::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Claims `range` for exclusive access until the returned reservation is
+    /// dropped.
+    pub fn reserve(&self, range: Range<usize>) -> Result<Reservation<'_>, ReserveError> {
+        let Range { start, end } = range;
+        if start > end || end > self.data.len() {
+            return Err(ReserveError::OutOfBounds { start, end, len: self.data.len() });
+        }
+
+        // Empty ranges cover no bytes, so they are neither checked nor
+        // recorded
+        if start < end {
+            // The lock guards no user code, so a poisoned list is still
+            // consistent
+            let mut reserved = self.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+            if reserved.iter().any(|held| held.start < end && start < held.end) {
+                return Err(ReserveError::Overlap { start, end });
+            }
+            reserved.push(start..end);
+        }
+
+        // `UnsafeCell<u8>` has the layout of `u8`, and the pointer is derived
+        // from the cells, so writing through it is allowed
+        let ptr = UnsafeCell::raw_get(self.data[start..end].as_ptr());
+        Ok(Reservation {
+            buffer: self,
+            range: start..end,
+            ptr: NonNull::new(ptr).expect("slice pointers are non-null"),
+        })
+    }
+
+    /// Reads one byte, failing if a writer currently holds it.
+    pub fn get(&self, index: usize) -> Result<u8, ReserveError> {
+        let end = index.saturating_add(1);
+        if index >= self.data.len() {
+            return Err(ReserveError::OutOfBounds { start: index, end, len: self.data.len() });
+        }
+        Ok(self.reserve(index..end)?[0])
+    }
+
+    /// All bytes, with no reservations possible while the borrow lasts.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        // Unique access to the cells gives unique access to their contents
+        unsafe { slice::from_raw_parts_mut(UnsafeCell::raw_get(self.data.as_ptr()), self.data.len()) }
+    }
+}
+
+impl fmt::Debug for SharedBufferMut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedBufferMut").field("len", &self.data.len()).finish_non_exhaustive()
+    }
+}
+
+/// Exclusive access to a byte range of a [`SharedBufferMut`], released on
+/// drop.
+pub struct Reservation<'a> {
+    buffer: &'a SharedBufferMut,
+    range: Range<usize>,
+    ptr: NonNull<u8>,
+}
+
+// A reservation is a unique borrow of its bytes, like `&mut [u8]`, and is
+// released through the buffer's lock, so it can move to and be shared with
+// other threads
+unsafe impl Send for Reservation<'_> {}
+unsafe impl Sync for Reservation<'_> {}
+
+impl Reservation<'_> {
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl Deref for Reservation<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // No other reservation covers these bytes while this one is held
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.range.len()) }
+    }
+}
+
+impl DerefMut for Reservation<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // As above, and `&mut self` rules out other borrows of this one
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.range.len()) }
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.range.is_empty() {
+            return;
+        }
+        let mut reserved = self.buffer.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = reserved.iter().position(|held| *held == self.range).expect("reservation is registered");
+        reserved.swap_remove(index);
+    }
+}
+
+impl fmt::Debug for Reservation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Reservation").field("range", &self.range).finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AlignedBufferError {
+    #[error("invalid buffer layout: {0}")]
+    InvalidLayout(#[from] LayoutError),
+    #[error("failed to allocate {size} bytes aligned to {align}")]
+    OutOfMemory { size: usize, align: usize },
+}
+
+/// Zeroed, heap-allocated bytes whose start is aligned to a chosen power of
+/// two.
+///
+/// This is the way to hand owned bytes to the zero-copy parser: allocate
+/// with `AlignedBuffer::new(len, RECORD_ALIGNMENT)`, write records into it
+/// with `AccountWriter` and parse them in place. A `Vec<u8>` or `Arc<[u8]>`
+/// only guarantees 1-byte alignment, so records in a multi-account buffer
+/// would land on arbitrary addresses; in an aligned buffer every record
+/// offset that is a multiple of `RECORD_ALIGNMENT` is also an aligned
+/// address, the same placement as a mapped account file. The headers
+/// themselves have alignment 1, so the parser still accepts a slice at any
+/// address, such as a sub-slice of a packet.
+///
+/// Larger alignments serve SIMD loads, `O_DIRECT` buffers and cache-line
+/// placement; [`BufferPool`] leases and the chunks in [`arena::Arena`] are
+/// backed by this type.
+///
+/// ```
+/// # use sonic_test::unsafe_rust_memory_management::AlignedBuffer;
+/// # use sonic_test::zero_copy_deserialization::{Account, AccountWriter, RECORD_ALIGNMENT};
+/// let writer = AccountWriter::new().amount(42).data(b"Hello");
+/// let mut buffer = AlignedBuffer::new(writer.encoded_len(), RECORD_ALIGNMENT);
+/// writer.write(&mut buffer).unwrap();
+/// assert_eq!(Account::from_bytes(&buffer).unwrap().amount(), 42);
+/// ```
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    align: usize,
+}
+
+// The buffer uniquely owns its allocation and has no interior mutability,
+// exactly like `Box<[u8]>`: moving it to another thread moves ownership, and
+// `&AlignedBuffer` only allows reads, while writes need `&mut`
+unsafe impl Send for AlignedBuffer {}
+unsafe impl Sync for AlignedBuffer {}
+
+impl AlignedBuffer {
+    /// Allocates `len` zeroed bytes aligned to `align`.
+    ///
+    /// Panics if `align` is not a power of two or the size overflows, and
+    /// reports allocation failure through `handle_alloc_error` like `Vec`.
+    pub fn new(len: usize, align: usize) -> Self {
+        match Self::try_new(len, align) {
+            Ok(buffer) => buffer,
+            Err(AlignedBufferError::OutOfMemory { .. }) => {
+                alloc::handle_alloc_error(Layout::from_size_align(len, align).unwrap())
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like [`AlignedBuffer::new`], but returns allocation failure instead
+    /// of aborting.
+    pub fn try_new(len: usize, align: usize) -> Result<Self, AlignedBufferError> {
+        let layout = Layout::from_size_align(len, align)?;
+
+        // Zero-size allocations are undefined behaviour; an aligned dangling
+        // pointer is valid for empty slices
+        if len == 0 {
+            let ptr = NonNull::new(ptr::without_provenance_mut(align)).expect("alignment is non-zero");
+            return Ok(AlignedBuffer { ptr, len, align });
+        }
+
+        // `alloc_zeroed` returns null on failure, and zeroing means every
+        // byte is initialised before a slice is ever created over it
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).ok_or(AlignedBufferError::OutOfMemory { size: len, align })?;
+        Ok(AlignedBuffer { ptr, len, align })
+    }
+
+    /// Copies `bytes` into a new buffer aligned to `align`.
+    pub fn from_slice(bytes: &[u8], align: usize) -> Self {
+        let mut buffer = Self::new(bytes.len(), align);
+        buffer.copy_from_slice(bytes);
+        buffer
+    }
+
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // `ptr` is valid for `len` initialised bytes for as long as `self`
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // As above, and `&mut self` guarantees the access is unique
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // Same layout as the allocation in `try_new`
+            let layout = Layout::from_size_align(self.len, self.align).expect("validated in try_new");
+            unsafe { alloc::dealloc(self.ptr.as_ptr(), layout) };
+        }
+    }
+}
+
+impl fmt::Debug for AlignedBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AlignedBuffer").field("len", &self.len).field("align", &self.align).finish()
+    }
+}
+
+/// Largest packet payload Solana sends, sized to fit an IPv6 MTU.
+pub const PACKET_DATA_SIZE: usize = 1232;
+
+/// Packets a pipeline stage usually handles at once.
+pub const PACKET_BATCH_SIZE: usize = 64;
+
+/// Cache line size on x86-64 and most ARM cores.
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// Counters reported by [`BufferPool::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Leases currently held.
+    pub in_use: usize,
+    /// Buffers waiting in the pool.
+    pub idle: usize,
+    /// Buffers allocated so far; never more than the capacity.
+    pub allocated: usize,
+    /// Leases served from a returned buffer instead of a new allocation.
+    pub recycled: u64,
+}
+
+/// Recycler for fixed-size buffers, such as packet buffers passed between
+/// pipeline stages.
+///
+/// Buffers are [`AlignedBuffer`]s allocated on first use, at most `capacity`
+/// of them, and returned to the pool when their [`Lease`] is dropped. Cloning
+/// the pool shares it.
+///
+/// The pool owns `AlignedBuffer`s rather than [`SharedBuffer`]s: a lease is
+/// written by one stage at a time and has to come back uniquely owned to be
+/// cleared and reused, while a `SharedBuffer` is immutable and may still be
+/// referenced elsewhere.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    buffer_len: usize,
+    align: usize,
+    capacity: usize,
+    state: Mutex<PoolState>,
+    returned: Condvar,
+}
+
+struct PoolState {
+    idle: Vec<AlignedBuffer>,
+    allocated: usize,
+    recycled: u64,
+}
+
+impl BufferPool {
+    /// Pool of up to `capacity` buffers of `buffer_len` bytes, aligned to
+    /// `align`.
+    ///
+    /// Panics if `capacity` is zero, since `lease` could then never return,
+    /// or if `align` is not a power of two.
+    pub fn new(buffer_len: usize, align: usize, capacity: usize) -> Self {
+        assert!(capacity > 0, "buffer pool capacity must be at least 1");
+        // Fail on a bad alignment now rather than on the first lease
+        Layout::from_size_align(buffer_len, align).expect("invalid buffer layout");
+        BufferPool {
+            inner: Arc::new(PoolInner {
+                buffer_len,
+                align,
+                capacity,
+                state: Mutex::new(PoolState { idle: Vec::with_capacity(capacity), allocated: 0, recycled: 0 }),
+                returned: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Pool of `PACKET_DATA_SIZE` buffers, each starting on its own cache
+    /// line so stages working on neighbouring buffers do not share lines.
+    pub fn packets(capacity: usize) -> Self {
+        Self::new(PACKET_DATA_SIZE, CACHE_LINE_SIZE, capacity)
+    }
+
+    pub fn buffer_len(&self) -> usize {
+        self.inner.buffer_len
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// A zeroed buffer, or `None` if all `capacity` buffers are leased.
+    pub fn try_lease(&self) -> Option<Lease> {
+        let state = self.inner.lock();
+        self.lease_from(state)
+    }
+
+    /// A zeroed buffer, waiting for one to be returned if all are leased.
+    pub fn lease(&self) -> Lease {
+        let mut state = self.inner.lock();
+        while state.idle.is_empty() && state.allocated == self.inner.capacity {
+            state = self.inner.returned.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+        self.lease_from(state).expect("a buffer is idle or can be allocated")
+    }
+
+    fn lease_from(&self, mut state: MutexGuard<'_, PoolState>) -> Option<Lease> {
+        let buffer = if let Some(buffer) = state.idle.pop() {
+            state.recycled += 1;
+            buffer
+        } else if state.allocated < self.inner.capacity {
+            state.allocated += 1;
+            // Allocate outside the lock; the slot is already counted
+            drop(state);
+            AlignedBuffer::new(self.inner.buffer_len, self.inner.align)
+        } else {
+            return None;
+        };
+        Some(Lease { buffer: Some(buffer), pool: Arc::clone(&self.inner) })
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let state = self.inner.lock();
+        PoolStats {
+            in_use: state.allocated - state.idle.len(),
+            idle: state.idle.len(),
+            allocated: state.allocated,
+            recycled: state.recycled,
+        }
+    }
+}
+
+impl PoolInner {
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        // Only counters and the idle list live under the lock, and they are
+        // updated together, so a poisoned state is still consistent
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("buffer_len", &self.inner.buffer_len)
+            .field("capacity", &self.inner.capacity)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`]. Dropping it zeroes the buffer and
+/// returns it to the pool.
+pub struct Lease {
+    // Only `None` while being returned in `drop`
+    buffer: Option<AlignedBuffer>,
+    pool: Arc<PoolInner>,
+}
+
+impl Deref for Lease {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buffer.as_deref().expect("leased until dropped")
+    }
+}
+
+impl DerefMut for Lease {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_deref_mut().expect("leased until dropped")
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut buffer = self.buffer.take().expect("leased until dropped");
+        // Clear before returning so the next holder cannot see this packet
+        buffer.fill(0);
+        self.pool.lock().idle.push(buffer);
+        self.pool.returned.notify_one();
+    }
+}
+
+impl fmt::Debug for Lease {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Lease").field("len", &self.len()).finish()
+    }
+}
+
+pub fn main() {
     let buffer = Arc::new(SharedBuffer::new(1024));
 
     let handles: Vec<_> = (0..10)
@@ -35,4 +500,109 @@ fn main() {
     for handle in handles {
         handle.join().unwrap();
     }
+
+    // Each thread fills its own 64-byte range of a shared writable buffer
+    let mut writable = SharedBufferMut::new(512);
+    thread::scope(|scope| {
+        for i in 0..8 {
+            let writable = &writable;
+            scope.spawn(move || {
+                let mut range = writable.reserve(i * 64..(i + 1) * 64).unwrap();
+                range.fill(i as u8);
+            });
+        }
+    });
+    let overlapping = writable.reserve(0..64).map(|_held| writable.reserve(32..96).is_err());
+    let filled = writable.as_mut_slice().chunks(64).enumerate().all(|(i, chunk)| chunk.iter().all(|&b| b == i as u8));
+    println!(
+        "SharedBufferMut: 8 threads wrote disjoint ranges: {}, overlapping reservation refused: {}",
+        filled,
+        overlapping == Ok(true)
+    );
+
+    // A fetch stage fills packet batches from a bounded pool and hands them to
+    // a verify stage over a ring buffer; verify drops them back into the pool
+    let pool = BufferPool::packets(2 * PACKET_BATCH_SIZE);
+    let (sender, receiver) = ring_buffer::spsc::channel::<Vec<Lease>>(1);
+    let verify = thread::spawn(move || {
+        std::iter::from_fn(|| receiver.recv().ok()).map(|batch| batch.len()).sum::<usize>()
+    });
+    for batch in 0..8u8 {
+        let packets = (0..PACKET_BATCH_SIZE)
+            .map(|_| {
+                let mut packet = pool.lease();
+                packet[0] = batch;
+                packet
+            })
+            .collect();
+        sender.send(packets).unwrap();
+    }
+    drop(sender);
+    let verified = verify.join().unwrap();
+    let stats = pool.stats();
+    println!(
+        "BufferPool: {} packets through {} buffers ({} recycled), {} still leased",
+        verified, stats.allocated, stats.recycled, stats.in_use
+    );
+    let held: Vec<_> = (0..pool.capacity()).map(|_| pool.lease()).collect();
+    println!("  try_lease refused once all buffers are leased: {}", pool.try_lease().is_none());
+    drop(held);
+
+    // Several sigverify workers share one queue; a full queue is reported to
+    // the sender instead of growing
+    let (sender, receiver) = ring_buffer::mpmc::channel::<u64>(64);
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let receiver = receiver.clone();
+            thread::spawn(move || std::iter::from_fn(|| receiver.recv().ok()).count())
+        })
+        .collect();
+    drop(receiver);
+    for slot in 0..10_000 {
+        sender.send(slot).unwrap();
+    }
+    drop(sender);
+    let received: usize = workers.into_iter().map(|worker| worker.join().unwrap()).sum();
+    let (sender, _receiver) = ring_buffer::mpmc::channel(1);
+    sender.send(0u64).unwrap();
+    println!(
+        "Ring buffers: 4 workers received {} of 10000 values, full channel signals backpressure: {}",
+        received,
+        matches!(sender.try_send(1), Err(ring_buffer::TrySendError::Full(1)))
+    );
+
+    // A second mapping of the same memfd stands in for another process
+    #[cfg(target_os = "linux")]
+    {
+        use shared_memory::{Access, SharedMemoryBuffer, SharedMemoryError};
+
+        let writer = SharedMemoryBuffer::create("sonic-test-demo", 64).unwrap();
+        writer.write(0, b"account bytes").unwrap();
+        let fd = writer.try_clone_read_only().unwrap();
+        let reader = SharedMemoryBuffer::open(fd, Access::ReadOnly).unwrap();
+        let mut snapshot = [0u8; 13];
+        let sequence = reader.read(0, &mut snapshot).unwrap();
+        println!(
+            "SharedMemoryBuffer: read-only mapping read {:?} at sequence {}, write refused: {}",
+            String::from_utf8_lossy(&snapshot),
+            sequence,
+            matches!(reader.write(0, b"x"), Err(SharedMemoryError::ReadOnly))
+        );
+    }
+
+    let aligned = Arc::new(AlignedBuffer::new(1024, 64));
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let buf = aligned.clone();
+            thread::spawn(move || buf[i * 256])
+        })
+        .collect();
+    let sum: u32 = handles.into_iter().map(|handle| handle.join().unwrap() as u32).sum();
+    println!(
+        "AlignedBuffer of {} bytes at a multiple of {}: {}, zeroed: {}",
+        aligned.len(),
+        aligned.align(),
+        (aligned.as_ptr() as usize).is_multiple_of(aligned.align()),
+        sum == 0
+    );
 }
@@ -0,0 +1,127 @@
+// Global allocator wrapper that counts allocations per thread, so tests can
+// prove a code path never touches the heap.
+//
+// Install it in a test binary and wrap the code under test:
+//
+//     #[global_allocator]
+//     static GLOBAL: TrackingAlloc = TrackingAlloc::system();
+//
+//     let account = assert_no_alloc(|| Account::from_bytes(&bytes).unwrap());
+//
+// Counters are thread-local, so allocations made by other threads, such as
+// the test harness reporting progress, never show up in another thread's
+// counts. Only compiled with the `alloc-tracking` feature.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+thread_local! {
+    // Const-initialised `Cell`s need no lazy initialisation or destructor, so
+    // touching them from inside the allocator cannot recurse into it
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static BYTES: Cell<u64> = const { Cell::new(0) };
+}
+
+// Set by the first allocation through a `TrackingAlloc`
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Allocations made on one thread.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AllocStats {
+    /// Calls to `alloc`, `alloc_zeroed` and `realloc`
+    pub allocations: u64,
+    /// Bytes requested by those calls
+    pub bytes: u64,
+}
+
+/// A `GlobalAlloc` that forwards to `A` and counts every allocation made on
+/// the calling thread.
+#[derive(Debug, Default)]
+pub struct TrackingAlloc<A = System> {
+    inner: A,
+}
+
+impl TrackingAlloc<System> {
+    /// Tracks the system allocator.
+    pub const fn system() -> Self {
+        TrackingAlloc { inner: System }
+    }
+}
+
+impl<A> TrackingAlloc<A> {
+    pub const fn new(inner: A) -> Self {
+        TrackingAlloc { inner }
+    }
+
+    fn record(size: usize) {
+        INSTALLED.store(true, Ordering::Relaxed);
+        // `try_with` fails only while the thread's locals are being torn
+        // down; allocations made then are not counted
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        let _ = BYTES.try_with(|bytes| bytes.set(bytes.get() + size as u64));
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::record(layout.size());
+        unsafe { self.inner.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::record(layout.size());
+        unsafe { self.inner.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.inner.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::record(new_size);
+        unsafe { self.inner.realloc(ptr, layout, new_size) }
+    }
+}
+
+/// Allocations made on this thread so far.
+pub fn thread_stats() -> AllocStats {
+    AllocStats {
+        allocations: ALLOCATIONS.with(Cell::get),
+        bytes: BYTES.with(Cell::get),
+    }
+}
+
+/// Runs `f` and returns its result with the allocations it made on this
+/// thread.
+///
+/// Panics if no `TrackingAlloc` is the global allocator, since every count
+/// would then be zero.
+#[track_caller]
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, AllocStats) {
+    assert!(
+        INSTALLED.load(Ordering::Relaxed),
+        "TrackingAlloc is not installed as the #[global_allocator]"
+    );
+    let before = thread_stats();
+    let result = f();
+    let after = thread_stats();
+    let stats = AllocStats {
+        allocations: after.allocations - before.allocations,
+        bytes: after.bytes - before.bytes,
+    };
+    (result, stats)
+}
+
+/// Runs `f` and panics if it allocated on this thread.
+#[track_caller]
+pub fn assert_no_alloc<R>(f: impl FnOnce() -> R) -> R {
+    let (result, stats) = count_allocations(f);
+    assert!(
+        stats.allocations == 0,
+        "expected no allocations, got {} ({} bytes)",
+        stats.allocations,
+        stats.bytes
+    );
+    result
+}
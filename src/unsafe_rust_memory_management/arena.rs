@@ -0,0 +1,192 @@
+// Bump allocator for data that lives exactly as long as one transaction.
+//
+// Allocation bumps a cursor through a chunk of memory and hands out a slice
+// borrowed from the arena; nothing is freed individually. `reset` releases
+// everything at once and keeps the largest chunk, so an arena reused across
+// transactions stops allocating once it has grown to the working set.
+//
+// Only `Copy` values can be placed in the arena. Dropping or resetting it never
+// runs destructors, and `Copy` types have none to run.
+
+use std::alloc::Layout;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+use super::AlignedBuffer;
+
+/// Size of the first chunk when none was requested
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// Alignment of every chunk; larger alignments are padded inside the chunk
+const CHUNK_ALIGN: usize = 16;
+
+/// A bump allocator whose allocations borrow the arena.
+///
+/// ```
+/// # use sonic_test::unsafe_rust_memory_management::arena::Arena;
+/// let mut arena = Arena::new();
+/// let data = arena.alloc_slice_copy(b"account data");
+/// data[0] = b'A';
+/// let lamports = arena.alloc(1_000u64);
+/// *lamports -= 100;
+/// assert_eq!(&data[..7], b"Account");
+///
+/// // Frees both allocations and keeps the chunk for the next transaction
+/// arena.reset();
+/// assert_eq!(arena.allocated_bytes(), 0);
+/// ```
+pub struct Arena {
+    // Chunks are only pushed while allocations are live. Pushing moves the
+    // `AlignedBuffer` handles, never the memory they own.
+    chunks: RefCell<Vec<AlignedBuffer>>,
+    // Offset of the first free byte in the last chunk
+    cursor: Cell<usize>,
+    // Bytes handed out since the last reset, including alignment padding
+    allocated: Cell<usize>,
+}
+
+impl Arena {
+    /// Creates an empty arena. The first allocation allocates a chunk.
+    pub fn new() -> Self {
+        Arena {
+            chunks: RefCell::new(Vec::new()),
+            cursor: Cell::new(0),
+            allocated: Cell::new(0),
+        }
+    }
+
+    /// Creates an arena whose first chunk holds `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let arena = Self::new();
+        if capacity > 0 {
+            arena.chunks.borrow_mut().push(AlignedBuffer::new(capacity, CHUNK_ALIGN));
+        }
+        arena
+    }
+
+    /// Moves `value` into the arena.
+    // Every call returns a fresh allocation that nothing else can reach, so
+    // handing out `&mut` from `&self` cannot alias
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T: Copy>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_layout(Layout::new::<T>()).cast::<T>();
+        // The allocation is sized and aligned for `T` and not handed out
+        // anywhere else
+        unsafe {
+            ptr.as_ptr().write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// Copies `src` into the arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let ptr = self.alloc_array::<T>(src.len());
+        // The allocation holds `src.len()` values of `T` and cannot overlap
+        // `src`, which is borrowed from outside the free part of the arena
+        unsafe {
+            ptr.as_ptr().copy_from_nonoverlapping(src.as_ptr(), src.len());
+            slice::from_raw_parts_mut(ptr.as_ptr(), src.len())
+        }
+    }
+
+    /// Allocates `len` copies of `value`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_fill<T: Copy>(&self, len: usize, value: T) -> &mut [T] {
+        let ptr = self.alloc_array::<T>(len);
+        // As above; every element is written before the slice is created
+        unsafe {
+            for i in 0..len {
+                ptr.as_ptr().add(i).write(value);
+            }
+            slice::from_raw_parts_mut(ptr.as_ptr(), len)
+        }
+    }
+
+    /// Frees every allocation. Keeps the largest chunk so an arena reused
+    /// for similar work stops allocating.
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        if let Some(largest) = chunks.pop() {
+            chunks.clear();
+            chunks.push(largest);
+        }
+        self.cursor.set(0);
+        self.allocated.set(0);
+    }
+
+    /// Bytes handed out since the last reset, including alignment padding.
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated.get()
+    }
+
+    /// Total size of the chunks the arena holds.
+    pub fn capacity(&self) -> usize {
+        self.chunks.borrow().iter().map(|chunk| chunk.len).sum()
+    }
+
+    fn alloc_array<T>(&self, len: usize) -> NonNull<T> {
+        let layout = Layout::array::<T>(len).expect("arena allocation too large");
+        self.alloc_layout(layout).cast()
+    }
+
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        // Zero-size values need no memory, only an aligned address
+        if layout.size() == 0 {
+            return NonNull::new(ptr::without_provenance_mut(layout.align())).expect("alignment is non-zero");
+        }
+
+        let mut chunks = self.chunks.borrow_mut();
+        let cursor = self.cursor.get();
+        if let Some(chunk) = chunks.last()
+            && let Some(offset) = Self::fit(chunk, cursor, layout)
+        {
+            return self.bump(chunk, cursor, offset, layout);
+        }
+
+        // Chunks double in size so the number of chunks stays logarithmic in
+        // the bytes allocated
+        let previous = chunks.last().map_or(DEFAULT_CHUNK_SIZE / 2, |chunk| chunk.len);
+        let len = (previous * 2).max(layout.size() + layout.align().saturating_sub(CHUNK_ALIGN));
+        chunks.push(AlignedBuffer::new(len, CHUNK_ALIGN.max(layout.align())));
+        let chunk = chunks.last().expect("chunk was just pushed");
+        let offset = Self::fit(chunk, 0, layout).expect("new chunk fits the allocation");
+        self.bump(chunk, 0, offset, layout)
+    }
+
+    // Offset of an allocation of `layout` at or after `cursor`, if it fits
+    fn fit(chunk: &AlignedBuffer, cursor: usize, layout: Layout) -> Option<usize> {
+        let address = (chunk.ptr.as_ptr() as usize).checked_add(cursor)?;
+        let offset = cursor + (address.next_multiple_of(layout.align()) - address);
+        (offset.checked_add(layout.size())? <= chunk.len).then_some(offset)
+    }
+
+    fn bump(&self, chunk: &AlignedBuffer, cursor: usize, offset: usize, layout: Layout) -> NonNull<u8> {
+        let end = offset + layout.size();
+        self.cursor.set(end);
+        self.allocated.set(self.allocated.get() + end - cursor);
+        // `fit` checked that `offset..end` is inside the chunk. The pointer is
+        // derived from the chunk's raw pointer, never from a slice over the
+        // chunk, so it does not alias earlier allocations. Lengths are read
+        // from the `len` field for the same reason.
+        unsafe { chunk.ptr.add(offset) }
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Arena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Arena")
+            .field("allocated", &self.allocated.get())
+            .field("capacity", &self.capacity())
+            .field("chunks", &self.chunks.borrow().len())
+            .finish()
+    }
+}
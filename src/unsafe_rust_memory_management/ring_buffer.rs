@@ -0,0 +1,670 @@
+// Bounded lock-free channels for handing packet batches between pipeline
+// stages.
+//
+// `spsc` is a single-producer single-consumer ring: each side owns one index,
+// so sending and receiving are a load, a slot access and a store. `mpmc` is
+// the bounded queue by Dmitry Vyukov, where every slot carries a sequence
+// number that tells producers and consumers whose turn it is.
+//
+// Both are bounded, and a full channel is reported to the sender
+// (`TrySendError::Full`) rather than buffered, which is the backpressure
+// signal an upstream stage reacts to. Blocking `send`/`recv` spin briefly,
+// yield a few times, and then park on a condition variable until the other
+// side frees a slot, fills one, or disconnects.
+//
+// Atomics and cells come from `sync` below, which switches to `loom` under
+// `--cfg loom` so the interleaving tests in `tests/ring_buffer_loom.rs` can
+// explore every ordering.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+
+use self::sync::{fence, Arc, AtomicBool, AtomicUsize, Condvar, Mutex, Ordering, UnsafeCell};
+
+#[cfg(not(loom))]
+mod sync {
+    pub use std::sync::{Arc, Condvar, Mutex};
+    pub use std::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+    pub use std::thread::yield_now;
+
+    // The subset of `loom::cell::UnsafeCell` used here
+    pub struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+    impl<T> UnsafeCell<T> {
+        pub fn new(value: T) -> Self {
+            UnsafeCell(std::cell::UnsafeCell::new(value))
+        }
+
+        pub fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+            f(self.0.get())
+        }
+    }
+}
+
+#[cfg(loom)]
+mod sync {
+    pub use loom::cell::UnsafeCell;
+    pub use loom::sync::{Arc, Condvar, Mutex};
+    pub use loom::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+    pub use loom::thread::yield_now;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum TrySendError<T> {
+    /// The channel is at capacity; the receiver has fallen behind.
+    #[error("channel is full")]
+    Full(T),
+    #[error("receiver has disconnected")]
+    Disconnected(T),
+}
+
+impl<T> TrySendError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(value) | TrySendError::Disconnected(value) => value,
+        }
+    }
+}
+
+/// The receiver disconnected; the unsent value is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("receiver has disconnected")]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum TryRecvError {
+    #[error("channel is empty")]
+    Empty,
+    #[error("channel is empty and every sender has disconnected")]
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("channel is empty and every sender has disconnected")]
+pub struct RecvError;
+
+// Keeps the producer and consumer indices on separate cache lines
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+// Spins for a few rounds, then yields to the scheduler for a few more, then
+// tells the caller to park. Under loom the caller parks straight away, since
+// loom needs a yield to make progress in a retry loop and parking is the path
+// worth exploring.
+struct Backoff(u32);
+
+impl Backoff {
+    const SPIN_LIMIT: u32 = 6;
+    const YIELD_LIMIT: u32 = 10;
+
+    fn new() -> Self {
+        Backoff(0)
+    }
+
+    // Returns false once the caller should stop retrying and park
+    fn snooze(&mut self) -> bool {
+        if cfg!(loom) || self.0 >= Self::YIELD_LIMIT {
+            return false;
+        }
+        if self.0 >= Self::SPIN_LIMIT {
+            sync::yield_now();
+        } else {
+            for _ in 0..1 << self.0 {
+                std::hint::spin_loop();
+            }
+        }
+        self.0 += 1;
+        true
+    }
+}
+
+// Where blocked senders or receivers park until the other side changes the
+// channel. Waiters register before they re-check the channel, and notifiers
+// check for waiters after they change it, with a SeqCst fence on both sides,
+// so either the waiter sees the change or the notifier sees the waiter. The
+// generation counter, bumped under the lock, closes the gap between the
+// waiter's re-check and its wait. The channel operations run outside the
+// lock, so a waiter never holds one side's lock while notifying the other.
+struct Parker {
+    waiters: AtomicUsize,
+    generation: Mutex<usize>,
+    condvar: Condvar,
+}
+
+impl Parker {
+    fn new() -> Self {
+        Parker { waiters: AtomicUsize::new(0), generation: Mutex::new(0), condvar: Condvar::new() }
+    }
+
+    // Parks until `ready` returns a value, re-checking after every wake-up
+    fn wait_until<R>(&self, mut ready: impl FnMut() -> Option<R>) -> R {
+        loop {
+            self.waiters.fetch_add(1, Ordering::SeqCst);
+            let generation = *self.generation.lock().unwrap();
+            fence(Ordering::SeqCst);
+            if let Some(value) = ready() {
+                self.waiters.fetch_sub(1, Ordering::SeqCst);
+                return value;
+            }
+
+            let mut current = self.generation.lock().unwrap();
+            while *current == generation {
+                current = self.condvar.wait(current).unwrap();
+            }
+            drop(current);
+            self.waiters.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // Wakes every parked waiter. Costs a fence and a load when there are none.
+    fn notify(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) != 0 {
+            let mut generation = self.generation.lock().unwrap();
+            *generation = generation.wrapping_add(1);
+            drop(generation);
+            self.condvar.notify_all();
+        }
+    }
+}
+
+fn blocking_send<T>(
+    value: T,
+    mut try_send: impl FnMut(T) -> Result<(), TrySendError<T>>,
+    not_full: &Parker,
+) -> Result<(), SendError<T>> {
+    let mut value = Some(value);
+    let mut attempt = || match try_send(value.take().expect("a full channel hands the value back")) {
+        Ok(()) => Some(Ok(())),
+        Err(TrySendError::Full(returned)) => {
+            value = Some(returned);
+            None
+        }
+        Err(TrySendError::Disconnected(returned)) => Some(Err(SendError(returned))),
+    };
+
+    let mut backoff = Backoff::new();
+    loop {
+        if let Some(result) = attempt() {
+            return result;
+        }
+        if !backoff.snooze() {
+            return not_full.wait_until(attempt);
+        }
+    }
+}
+
+fn blocking_recv<T>(
+    mut try_recv: impl FnMut() -> Result<T, TryRecvError>,
+    not_empty: &Parker,
+) -> Result<T, RecvError> {
+    let mut attempt = || match try_recv() {
+        Ok(value) => Some(Ok(value)),
+        Err(TryRecvError::Empty) => None,
+        Err(TryRecvError::Disconnected) => Some(Err(RecvError)),
+    };
+
+    let mut backoff = Backoff::new();
+    loop {
+        if let Some(result) = attempt() {
+            return result;
+        }
+        if !backoff.snooze() {
+            return not_empty.wait_until(attempt);
+        }
+    }
+}
+
+pub mod spsc {
+    //! Bounded single-producer single-consumer channel.
+
+    use super::*;
+
+    struct Ring<T> {
+        slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+        // Free-running counters; the slot is the counter modulo capacity.
+        // `head` is written only by the receiver and `tail` only by the
+        // sender.
+        head: CachePadded<AtomicUsize>,
+        tail: CachePadded<AtomicUsize>,
+        sender_alive: AtomicBool,
+        receiver_alive: AtomicBool,
+        // A blocked sender parks on `not_full` and a blocked receiver on
+        // `not_empty`
+        not_full: Parker,
+        not_empty: Parker,
+    }
+
+    // Each slot is accessed by one side at a time, as handed over by the
+    // release/acquire pairs on `head` and `tail`, so values only need to be
+    // `Send` to cross between the two threads
+    unsafe impl<T: Send> Send for Ring<T> {}
+    unsafe impl<T: Send> Sync for Ring<T> {}
+
+    impl<T> Ring<T> {
+        fn slot(&self, position: usize) -> &UnsafeCell<MaybeUninit<T>> {
+            &self.slots[position % self.slots.len()]
+        }
+    }
+
+    impl<T> Drop for Ring<T> {
+        fn drop(&mut self) {
+            // Both sides are gone, so nothing else touches the slots
+            let head = self.head.load(Ordering::Relaxed);
+            let tail = self.tail.load(Ordering::Relaxed);
+            for position in head..tail {
+                self.slot(position).with_mut(|slot| unsafe { (*slot).assume_init_drop() });
+            }
+        }
+    }
+
+    /// Sending half. Not `Clone` or `Sync`: there is exactly one producer.
+    pub struct Sender<T> {
+        ring: Arc<Ring<T>>,
+        _not_sync: PhantomData<std::cell::Cell<()>>,
+    }
+
+    /// Receiving half. Not `Clone` or `Sync`: there is exactly one consumer.
+    pub struct Receiver<T> {
+        ring: Arc<Ring<T>>,
+        _not_sync: PhantomData<std::cell::Cell<()>>,
+    }
+
+    /// Creates a channel holding up to `capacity` values.
+    pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        assert!(capacity > 0, "channel capacity must be non-zero");
+        let ring = Arc::new(Ring {
+            slots: (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+            head: CachePadded(AtomicUsize::new(0)),
+            tail: CachePadded(AtomicUsize::new(0)),
+            sender_alive: AtomicBool::new(true),
+            receiver_alive: AtomicBool::new(true),
+            not_full: Parker::new(),
+            not_empty: Parker::new(),
+        });
+        (
+            Sender { ring: ring.clone(), _not_sync: PhantomData },
+            Receiver { ring, _not_sync: PhantomData },
+        )
+    }
+
+    impl<T> Sender<T> {
+        pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+            let ring = &*self.ring;
+            if !ring.receiver_alive.load(Ordering::Acquire) {
+                return Err(TrySendError::Disconnected(value));
+            }
+
+            let tail = ring.tail.load(Ordering::Relaxed);
+            // Acquire pairs with the receiver's release of `head`, so its
+            // read of the slot happens before it is overwritten
+            let head = ring.head.load(Ordering::Acquire);
+            if tail.wrapping_sub(head) == ring.slots.len() {
+                return Err(TrySendError::Full(value));
+            }
+
+            ring.slot(tail).with_mut(|slot| unsafe { (*slot).write(value) });
+            // Publishes the slot to the receiver
+            ring.tail.store(tail.wrapping_add(1), Ordering::Release);
+            ring.not_empty.notify();
+            Ok(())
+        }
+
+        /// Waits while the channel is full.
+        pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+            blocking_send(value, |value| self.try_send(value), &self.ring.not_full)
+        }
+
+        /// Values waiting to be received; a full channel means the receiver
+        /// is the bottleneck.
+        pub fn len(&self) -> usize {
+            let tail = self.ring.tail.load(Ordering::Relaxed);
+            tail.wrapping_sub(self.ring.head.load(Ordering::Acquire))
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        pub fn capacity(&self) -> usize {
+            self.ring.slots.len()
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn try_recv(&self) -> Result<T, TryRecvError> {
+            let ring = &*self.ring;
+            let head = ring.head.load(Ordering::Relaxed);
+            // Acquire pairs with the sender's release of `tail`, so the slot
+            // is fully written
+            let mut tail = ring.tail.load(Ordering::Acquire);
+            if head == tail {
+                if ring.sender_alive.load(Ordering::Acquire) {
+                    return Err(TryRecvError::Empty);
+                }
+                // The sender may have sent a last value before dropping
+                tail = ring.tail.load(Ordering::Acquire);
+                if head == tail {
+                    return Err(TryRecvError::Disconnected);
+                }
+            }
+
+            let value = ring.slot(head).with_mut(|slot| unsafe { (*slot).assume_init_read() });
+            // Hands the slot back to the sender
+            ring.head.store(head.wrapping_add(1), Ordering::Release);
+            ring.not_full.notify();
+            Ok(value)
+        }
+
+        /// Waits while the channel is empty.
+        pub fn recv(&self) -> Result<T, RecvError> {
+            blocking_recv(|| self.try_recv(), &self.ring.not_empty)
+        }
+
+        pub fn len(&self) -> usize {
+            let head = self.ring.head.load(Ordering::Relaxed);
+            self.ring.tail.load(Ordering::Acquire).wrapping_sub(head)
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        pub fn capacity(&self) -> usize {
+            self.ring.slots.len()
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            self.ring.sender_alive.store(false, Ordering::Release);
+            self.ring.not_empty.notify();
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            self.ring.receiver_alive.store(false, Ordering::Release);
+            self.ring.not_full.notify();
+        }
+    }
+
+    impl<T> fmt::Debug for Sender<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("spsc::Sender").field("capacity", &self.capacity()).finish_non_exhaustive()
+        }
+    }
+
+    impl<T> fmt::Debug for Receiver<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("spsc::Receiver").field("capacity", &self.capacity()).finish_non_exhaustive()
+        }
+    }
+}
+
+pub mod mpmc {
+    //! Bounded multi-producer multi-consumer channel.
+
+    use super::*;
+
+    struct Slot<T> {
+        // `free(position)` while waiting for the sender that claims
+        // `position`, and `ready(position)` once its value can be received.
+        // Plain `position`/`position + 1` would make a full slot look free
+        // to the next lap when the capacity is 1.
+        sequence: AtomicUsize,
+        value: UnsafeCell<MaybeUninit<T>>,
+    }
+
+    struct Queue<T> {
+        slots: Box<[Slot<T>]>,
+        enqueue: CachePadded<AtomicUsize>,
+        dequeue: CachePadded<AtomicUsize>,
+        senders: AtomicUsize,
+        receivers: AtomicUsize,
+        not_full: Parker,
+        not_empty: Parker,
+    }
+
+    // A slot's value is owned by whichever side won the position for it, as
+    // handed over by release/acquire on `sequence`
+    unsafe impl<T: Send> Send for Queue<T> {}
+    unsafe impl<T: Send> Sync for Queue<T> {}
+
+    fn free(position: usize) -> usize {
+        position.wrapping_mul(2)
+    }
+
+    fn ready(position: usize) -> usize {
+        free(position).wrapping_add(1)
+    }
+
+    // Signed distance from `b` to `a` on the free-running counters
+    fn distance(a: usize, b: usize) -> isize {
+        a.wrapping_sub(b) as isize
+    }
+
+    impl<T> Queue<T> {
+        fn slot(&self, position: usize) -> &Slot<T> {
+            &self.slots[position % self.slots.len()]
+        }
+
+        fn try_push(&self, value: T) -> Result<(), T> {
+            let mut position = self.enqueue.load(Ordering::Relaxed);
+            loop {
+                let slot = self.slot(position);
+                // Acquire pairs with the release by the receiver that freed
+                // the slot
+                let sequence = slot.sequence.load(Ordering::Acquire);
+                match distance(sequence, free(position)) {
+                    0 => match self.enqueue.compare_exchange_weak(
+                        position,
+                        position.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => {
+                            slot.value.with_mut(|cell| unsafe { (*cell).write(value) });
+                            slot.sequence.store(ready(position), Ordering::Release);
+                            return Ok(());
+                        }
+                        Err(current) => position = current,
+                    },
+                    // The slot still holds the value from one lap ago
+                    ..0 => return Err(value),
+                    // Another sender claimed this position; catch up
+                    _ => position = self.enqueue.load(Ordering::Relaxed),
+                }
+            }
+        }
+
+        fn try_pop(&self) -> Option<T> {
+            let mut position = self.dequeue.load(Ordering::Relaxed);
+            loop {
+                let slot = self.slot(position);
+                // Acquire pairs with the release by the sender that filled
+                // the slot
+                let sequence = slot.sequence.load(Ordering::Acquire);
+                match distance(sequence, ready(position)) {
+                    0 => match self.dequeue.compare_exchange_weak(
+                        position,
+                        position.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => {
+                            let value = slot.value.with_mut(|cell| unsafe { (*cell).assume_init_read() });
+                            // Free the slot for the sender one lap ahead
+                            slot.sequence.store(free(position.wrapping_add(self.slots.len())), Ordering::Release);
+                            return Some(value);
+                        }
+                        Err(current) => position = current,
+                    },
+                    // Nothing has been written at this position yet
+                    ..0 => return None,
+                    // Another receiver took this position; catch up
+                    _ => position = self.dequeue.load(Ordering::Relaxed),
+                }
+            }
+        }
+
+        fn len(&self) -> usize {
+            let dequeue = self.dequeue.load(Ordering::Relaxed);
+            let enqueue = self.enqueue.load(Ordering::Relaxed);
+            enqueue.wrapping_sub(dequeue).min(self.slots.len())
+        }
+    }
+
+    impl<T> Drop for Queue<T> {
+        fn drop(&mut self) {
+            while self.try_pop().is_some() {}
+        }
+    }
+
+    /// Sending half; clone it for more producers.
+    pub struct Sender<T> {
+        queue: Arc<Queue<T>>,
+    }
+
+    /// Receiving half; clone it for more consumers.
+    pub struct Receiver<T> {
+        queue: Arc<Queue<T>>,
+    }
+
+    /// Creates a channel holding up to `capacity` values.
+    pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        assert!(capacity > 0, "channel capacity must be non-zero");
+        let queue = Arc::new(Queue {
+            slots: (0..capacity)
+                .map(|position| Slot {
+                    sequence: AtomicUsize::new(free(position)),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect(),
+            enqueue: CachePadded(AtomicUsize::new(0)),
+            dequeue: CachePadded(AtomicUsize::new(0)),
+            senders: AtomicUsize::new(1),
+            receivers: AtomicUsize::new(1),
+            not_full: Parker::new(),
+            not_empty: Parker::new(),
+        });
+        (Sender { queue: queue.clone() }, Receiver { queue })
+    }
+
+    impl<T> Sender<T> {
+        pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+            if self.queue.receivers.load(Ordering::Acquire) == 0 {
+                return Err(TrySendError::Disconnected(value));
+            }
+            self.queue.try_push(value).map_err(TrySendError::Full)?;
+            self.queue.not_empty.notify();
+            Ok(())
+        }
+
+        /// Waits while the channel is full.
+        pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+            blocking_send(value, |value| self.try_send(value), &self.queue.not_full)
+        }
+
+        /// Approximate number of queued values; a full channel means the
+        /// receivers are the bottleneck.
+        pub fn len(&self) -> usize {
+            self.queue.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        pub fn capacity(&self) -> usize {
+            self.queue.slots.len()
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn try_recv(&self) -> Result<T, TryRecvError> {
+            if let Some(value) = self.queue.try_pop() {
+                self.queue.not_full.notify();
+                return Ok(value);
+            }
+            if self.queue.senders.load(Ordering::Acquire) != 0 {
+                return Err(TryRecvError::Empty);
+            }
+            // A last value may have been sent before the final sender dropped
+            let value = self.queue.try_pop().ok_or(TryRecvError::Disconnected)?;
+            self.queue.not_full.notify();
+            Ok(value)
+        }
+
+        /// Waits while the channel is empty.
+        pub fn recv(&self) -> Result<T, RecvError> {
+            blocking_recv(|| self.try_recv(), &self.queue.not_empty)
+        }
+
+        /// Approximate number of queued values.
+        pub fn len(&self) -> usize {
+            self.queue.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        pub fn capacity(&self) -> usize {
+            self.queue.slots.len()
+        }
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            self.queue.senders.fetch_add(1, Ordering::Relaxed);
+            Sender { queue: self.queue.clone() }
+        }
+    }
+
+    impl<T> Clone for Receiver<T> {
+        fn clone(&self) -> Self {
+            self.queue.receivers.fetch_add(1, Ordering::Relaxed);
+            Receiver { queue: self.queue.clone() }
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            // Release orders this sender's pushes before the count reaches 0
+            if self.queue.senders.fetch_sub(1, Ordering::Release) == 1 {
+                self.queue.not_empty.notify();
+            }
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            if self.queue.receivers.fetch_sub(1, Ordering::Release) == 1 {
+                self.queue.not_full.notify();
+            }
+        }
+    }
+
+    impl<T> fmt::Debug for Sender<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("mpmc::Sender").field("capacity", &self.capacity()).finish_non_exhaustive()
+        }
+    }
+
+    impl<T> fmt::Debug for Receiver<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("mpmc::Receiver").field("capacity", &self.capacity()).finish_non_exhaustive()
+        }
+    }
+}
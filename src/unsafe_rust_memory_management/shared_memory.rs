@@ -0,0 +1,424 @@
+// Buffer in an anonymous shared-memory file (`memfd_create(2)`) that several
+// processes map at once, such as an indexer and a validator sidecar reading
+// the same account bytes.
+//
+// The file starts with a 64-byte header holding a magic number, the data
+// length and a sequence lock, followed by the data:
+//
+// [magic: u64][sequence: u64][len: u64][padding][data: len]
+//
+// Writers make the sequence odd, copy their bytes and make it even again.
+// Readers copy the bytes out between two loads of the sequence and retry if
+// it changed or was odd, so a snapshot never mixes two writes. The creator
+// seals the file's size, so a mapping can never be cut short under a reader
+// (which would fault with `SIGBUS`).
+//
+// The descriptor moves to another process over a Unix socket
+// (`send_to`/`receive`). A peer that should only read is sent a descriptor
+// re-opened `O_RDONLY`, which the kernel refuses to map writable. That alone
+// does not stop a peer from re-opening the file read-write through
+// `/proc/<pid>/fd`, so a buffer with a single writer can also be sealed with
+// `F_SEAL_FUTURE_WRITE` (`create_exclusive_writer`), after which no new
+// writable mapping of the file can be made from any descriptor.
+
+use std::ffi::CString;
+use std::fmt;
+use std::hint;
+use std::io;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::ptr::{self, NonNull};
+use std::slice;
+use std::sync::atomic::{self, AtomicU8, AtomicU64, Ordering};
+use std::thread;
+
+/// Marks a file laid out by [`SharedMemoryBuffer::create`]
+const MAGIC: u64 = u64::from_le_bytes(*b"SONICSHM");
+
+/// Bytes before the data; one cache line, so the data stays 64-byte aligned
+pub const HEADER_SIZE: usize = 64;
+
+/// Seals that keep the file's size fixed for every process mapping it
+const SIZE_SEALS: libc::c_int = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW;
+
+/// Seal that leaves the creator's mapping as the only writable one
+const WRITE_SEAL: libc::c_int = libc::F_SEAL_FUTURE_WRITE;
+
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    sequence: AtomicU64,
+    len: AtomicU64,
+}
+
+const _: () = assert!(mem::size_of::<Header>() <= HEADER_SIZE);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SharedMemoryError {
+    #[error("shared memory I/O failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a shared memory buffer: {reason}")]
+    InvalidHeader { reason: &'static str },
+    #[error("buffer is mapped read-only")]
+    ReadOnly,
+    #[error("range {start}..{end} is out of bounds for a buffer of {len} bytes")]
+    OutOfBounds { start: usize, end: usize, len: usize },
+    #[error("a write was in progress or completed during the read")]
+    TornRead,
+}
+
+/// How a process maps the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Mapped `PROT_READ`; writes are refused and would fault.
+    ReadOnly,
+    ReadWrite,
+}
+
+/// A mapping of a sealed `memfd` with a sequence-lock header.
+///
+/// All access to the shared bytes goes through atomics, because other
+/// processes write them concurrently and Rust's memory model has no other
+/// way to express that. Atomic loads are also what makes reads through a
+/// read-only mapping sound.
+pub struct SharedMemoryBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    access: Access,
+    fd: OwnedFd,
+}
+
+// Every access to the mapping is atomic, and the mapping lives until `drop`
+unsafe impl Send for SharedMemoryBuffer {}
+unsafe impl Sync for SharedMemoryBuffer {}
+
+impl SharedMemoryBuffer {
+    /// Creates a zeroed buffer of `len` data bytes. `name` only shows up in
+    /// `/proc/<pid>/fd` and debugging output.
+    pub fn create(name: &str, len: usize) -> Result<Self, SharedMemoryError> {
+        Self::create_sealed(name, len, 0)
+    }
+
+    /// Like [`SharedMemoryBuffer::create`], but seals the file with
+    /// `F_SEAL_FUTURE_WRITE` once it is mapped, so the returned mapping is
+    /// the only one that can ever write. Every other mapping, in this or any
+    /// process and through any descriptor, has to be [`Access::ReadOnly`].
+    pub fn create_exclusive_writer(name: &str, len: usize) -> Result<Self, SharedMemoryError> {
+        Self::create_sealed(name, len, WRITE_SEAL)
+    }
+
+    fn create_sealed(name: &str, len: usize, seals: libc::c_int) -> Result<Self, SharedMemoryError> {
+        let name = CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // `memfd_create` returned a new descriptor that nothing else owns
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let file_len = HEADER_SIZE.checked_add(len).and_then(|n| libc::off_t::try_from(n).ok()).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "buffer length does not fit in a file")
+        })?;
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), file_len) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_ADD_SEALS, SIZE_SEALS) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        // The file is zero-filled, so the sequence starts out even
+        let buffer = Self::map(fd, len, Access::ReadWrite)?;
+        buffer.header().len.store(len as u64, Ordering::Relaxed);
+        buffer.header().magic.store(MAGIC, Ordering::Release);
+
+        // The write seal only blocks new writable mappings, so it goes on
+        // after ours exists. `F_SEAL_SEAL` stops peers adding seals later.
+        if unsafe { libc::fcntl(buffer.fd.as_raw_fd(), libc::F_ADD_SEALS, seals | libc::F_SEAL_SEAL) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(buffer)
+    }
+
+    /// Maps a buffer created by [`SharedMemoryBuffer::create`], usually in
+    /// another process. [`Access::ReadWrite`] fails with
+    /// [`SharedMemoryError::ReadOnly`] if `fd` was opened read-only or the
+    /// file is sealed against new writers.
+    pub fn open(fd: OwnedFd, access: Access) -> Result<Self, SharedMemoryError> {
+        // A file that could still shrink could fault a reader at any time
+        let seals = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GET_SEALS) };
+        if seals < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        if seals & SIZE_SEALS != SIZE_SEALS {
+            return Err(SharedMemoryError::InvalidHeader { reason: "file size is not sealed" });
+        }
+        if access == Access::ReadWrite {
+            let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) };
+            if flags < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            // `mmap` would refuse too, with a less specific error
+            if flags & libc::O_ACCMODE == libc::O_RDONLY || seals & WRITE_SEAL != 0 {
+                return Err(SharedMemoryError::ReadOnly);
+            }
+        }
+
+        let mut stat = mem::MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // `fstat` succeeded, so it filled in `stat`
+        let file_len = usize::try_from(unsafe { stat.assume_init() }.st_size)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "negative file size"))?;
+        let len = file_len
+            .checked_sub(HEADER_SIZE)
+            .ok_or(SharedMemoryError::InvalidHeader { reason: "file is shorter than the header" })?;
+
+        let buffer = Self::map(fd, len, access)?;
+        if buffer.header().magic.load(Ordering::Acquire) != MAGIC {
+            return Err(SharedMemoryError::InvalidHeader { reason: "bad magic" });
+        }
+        if buffer.header().len.load(Ordering::Relaxed) != len as u64 {
+            return Err(SharedMemoryError::InvalidHeader { reason: "length does not match the file size" });
+        }
+        Ok(buffer)
+    }
+
+    fn map(fd: OwnedFd, len: usize, access: Access) -> Result<Self, SharedMemoryError> {
+        let prot = match access {
+            Access::ReadOnly => libc::PROT_READ,
+            Access::ReadWrite => libc::PROT_READ | libc::PROT_WRITE,
+        };
+        let addr = unsafe { libc::mmap(ptr::null_mut(), HEADER_SIZE + len, prot, libc::MAP_SHARED, fd.as_raw_fd(), 0) };
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error().into());
+        }
+        let ptr = NonNull::new(addr as *mut u8).expect("mmap does not return null on success");
+        Ok(SharedMemoryBuffer { ptr, len, access, fd })
+    }
+
+    fn header(&self) -> &Header {
+        // The mapping starts with `HEADER_SIZE` bytes, is page aligned, and
+        // is only accessed through the atomics in `Header`
+        unsafe { &*(self.ptr.as_ptr() as *const Header) }
+    }
+
+    fn data(&self) -> &[AtomicU8] {
+        // `len` bytes follow the header; `AtomicU8` has the size and
+        // alignment of `u8`
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr().add(HEADER_SIZE) as *const AtomicU8, self.len) }
+    }
+
+    fn range(&self, offset: usize, len: usize) -> Result<&[AtomicU8], SharedMemoryError> {
+        let end = offset.checked_add(len).filter(|&end| end <= self.len);
+        end.map(|end| &self.data()[offset..end]).ok_or(SharedMemoryError::OutOfBounds {
+            start: offset,
+            end: offset.saturating_add(len),
+            len: self.len,
+        })
+    }
+
+    /// Data bytes, not counting the header.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn access(&self) -> Access {
+        self.access
+    }
+
+    /// Number of writes started so far, doubled; odd while a write is in
+    /// progress.
+    pub fn sequence(&self) -> u64 {
+        self.header().sequence.load(Ordering::Acquire)
+    }
+
+    /// Copies `bytes` in at `offset` under the sequence lock, waiting for
+    /// any write in progress in this or another process. Returns the new
+    /// sequence.
+    pub fn write(&self, offset: usize, bytes: &[u8]) -> Result<u64, SharedMemoryError> {
+        if self.access == Access::ReadOnly {
+            return Err(SharedMemoryError::ReadOnly);
+        }
+        let target = self.range(offset, bytes.len())?;
+        let sequence = &self.header().sequence;
+
+        // Taking the lock means moving the sequence from even to odd, which
+        // also keeps writers in different processes apart
+        let mut current = sequence.load(Ordering::Relaxed);
+        loop {
+            if current % 2 == 1 {
+                thread::yield_now();
+                current = sequence.load(Ordering::Relaxed);
+                continue;
+            }
+            match sequence.compare_exchange_weak(current, current + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+        // Orders the odd sequence before the data stores, so a reader that
+        // sees any new byte also sees the sequence change
+        atomic::fence(Ordering::Release);
+
+        for (byte, &value) in target.iter().zip(bytes) {
+            byte.store(value, Ordering::Relaxed);
+        }
+
+        sequence.store(current + 2, Ordering::Release);
+        Ok(current + 2)
+    }
+
+    /// Copies the bytes at `offset` into `buf` if no write overlaps the
+    /// copy. Returns the sequence the snapshot was taken at.
+    pub fn try_read(&self, offset: usize, buf: &mut [u8]) -> Result<u64, SharedMemoryError> {
+        let source = self.range(offset, buf.len())?;
+        let sequence = &self.header().sequence;
+
+        let before = sequence.load(Ordering::Acquire);
+        if before % 2 == 1 {
+            return Err(SharedMemoryError::TornRead);
+        }
+        for (value, byte) in buf.iter_mut().zip(source) {
+            *value = byte.load(Ordering::Relaxed);
+        }
+        // Pairs with the writer's release fence: if any byte came from a
+        // newer write, the reload below sees that write's odd sequence
+        atomic::fence(Ordering::Acquire);
+        if sequence.load(Ordering::Relaxed) != before {
+            return Err(SharedMemoryError::TornRead);
+        }
+        Ok(before)
+    }
+
+    /// Like [`SharedMemoryBuffer::try_read`], retrying until a snapshot
+    /// completes without a write overlapping it. Spins forever if a writer
+    /// died while holding the lock.
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> Result<u64, SharedMemoryError> {
+        let mut attempts = 0u32;
+        loop {
+            match self.try_read(offset, buf) {
+                Err(SharedMemoryError::TornRead) if attempts < 64 => hint::spin_loop(),
+                Err(SharedMemoryError::TornRead) => thread::yield_now(),
+                result => return result,
+            }
+            attempts = attempts.saturating_add(1);
+        }
+    }
+
+    /// A new descriptor for the same file, opened `O_RDONLY` so that it can
+    /// only be mapped [`Access::ReadOnly`].
+    pub fn try_clone_read_only(&self) -> io::Result<OwnedFd> {
+        // Opening the `/proc` link opens the file itself, with new flags,
+        // rather than duplicating this descriptor and its `O_RDWR`
+        let file = std::fs::File::open(format!("/proc/self/fd/{}", self.fd.as_raw_fd()))?;
+        Ok(file.into())
+    }
+
+    /// Sends a descriptor for the buffer over a Unix socket, to be mapped by
+    /// [`SharedMemoryBuffer::receive`] on the other end. With
+    /// [`Access::ReadOnly`] the peer gets a descriptor from
+    /// [`SharedMemoryBuffer::try_clone_read_only`], which the kernel will
+    /// not map writable. A peer can still re-open the file through `/proc`
+    /// unless it was made with
+    /// [`SharedMemoryBuffer::create_exclusive_writer`].
+    pub fn send_to(&self, socket: &UnixStream, access: Access) -> io::Result<()> {
+        let read_only;
+        let fd = match access {
+            Access::ReadOnly => {
+                read_only = self.try_clone_read_only()?;
+                read_only.as_raw_fd()
+            }
+            Access::ReadWrite => self.fd.as_raw_fd(),
+        };
+
+        // One data byte has to accompany the descriptor
+        let mut payload = [0u8];
+        let mut iov = libc::iovec { iov_base: payload.as_mut_ptr() as *mut libc::c_void, iov_len: 1 };
+        let mut control = Control::default();
+        let message = control.message(&mut iov);
+
+        // `control` has room for one header carrying one descriptor
+        unsafe {
+            let header = libc::CMSG_FIRSTHDR(&message);
+            (*header).cmsg_level = libc::SOL_SOCKET;
+            (*header).cmsg_type = libc::SCM_RIGHTS;
+            (*header).cmsg_len = libc::CMSG_LEN(mem::size_of::<libc::c_int>() as u32) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(header) as *mut libc::c_int, fd);
+        }
+
+        if unsafe { libc::sendmsg(socket.as_raw_fd(), &message, 0) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Receives a descriptor sent by [`SharedMemoryBuffer::send_to`] and
+    /// maps it.
+    pub fn receive(socket: &UnixStream, access: Access) -> Result<Self, SharedMemoryError> {
+        let mut payload = [0u8];
+        let mut iov = libc::iovec { iov_base: payload.as_mut_ptr() as *mut libc::c_void, iov_len: 1 };
+        let mut control = Control::default();
+        let mut message = control.message(&mut iov);
+
+        if unsafe { libc::recvmsg(socket.as_raw_fd(), &mut message, libc::MSG_CMSG_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        // The kernel filled in `control` up to `msg_controllen`
+        let fd = unsafe {
+            let header = libc::CMSG_FIRSTHDR(&message);
+            if header.is_null() || (*header).cmsg_level != libc::SOL_SOCKET || (*header).cmsg_type != libc::SCM_RIGHTS {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "message carried no descriptor").into());
+            }
+            // The received descriptor is new to this process and owned by us
+            OwnedFd::from_raw_fd(ptr::read_unaligned(libc::CMSG_DATA(header) as *const libc::c_int))
+        };
+        Self::open(fd, access)
+    }
+}
+
+// Control-message buffer for one descriptor, aligned for `cmsghdr`
+#[derive(Default)]
+struct Control([u64; 4]);
+
+const _: () = assert!(mem::size_of::<Control>() >= mem::size_of::<libc::cmsghdr>() + 2 * mem::size_of::<libc::c_int>());
+
+impl Control {
+    fn message(&mut self, iov: &mut libc::iovec) -> libc::msghdr {
+        // Every field not set below is null or zero
+        let mut message: libc::msghdr = unsafe { mem::zeroed() };
+        message.msg_iov = iov;
+        message.msg_iovlen = 1;
+        message.msg_control = self.0.as_mut_ptr() as *mut libc::c_void;
+        message.msg_controllen = unsafe { libc::CMSG_SPACE(mem::size_of::<libc::c_int>() as u32) } as _;
+        message
+    }
+}
+
+impl AsFd for SharedMemoryBuffer {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl Drop for SharedMemoryBuffer {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, HEADER_SIZE + self.len) };
+    }
+}
+
+impl fmt::Debug for SharedMemoryBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedMemoryBuffer")
+            .field("len", &self.len)
+            .field("access", &self.access)
+            .field("sequence", &self.sequence())
+            .finish()
+    }
+}
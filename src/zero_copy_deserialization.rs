@@ -1,130 +1,1014 @@
 // Account data layout:
 // [discriminator: u8][owner: Pubkey(32)][amount: u64][data_len: u32][data: Vec<u8>]
+//
+// `Account` overlays `AccountHeader`, which keeps the 56-byte `repr(C)`
+// layout with its padding spelled out. `PackedAccount` overlays
+// `PackedAccountHeader`, the documented layout byte for byte (45 bytes), which
+// is what on-chain buffers actually contain. Integers in both are stored
+// little-endian via `crate::endian`, so both headers have alignment 1 and
+// parse the same way on every host.
+//
+// `AccountHeader` carries a layout version in what used to be its first
+// padding byte, so buffers written before versioning read as version 0. Each
+// later version appends its fields after the previous version's header; the
+// data follows the whole versioned header.
 
-use std::mem;
+use core::fmt;
+use core::mem;
 
-#[derive(Debug, Clone)]
+use crate::endian::{LeU32, LeU64};
+use crate::pubkey::Pubkey;
+
+/// Overlays a `repr(C)` struct of `Pod` fields on account bytes.
+///
+/// ```
+/// # use sonic_test::zero_copy_deserialization::ZeroCopy;
+/// #[derive(ZeroCopy)]
+/// #[repr(C)]
+/// struct Entry {
+///     kind: u8,
+///     _padding: [u8; 7],
+///     value: u64,
+/// }
+///
+/// assert_eq!((Entry::SIZE, Entry::KIND_OFFSET, Entry::VALUE_OFFSET), (16, 0, 8));
+/// ```
+///
+/// Layouts that could expose uninitialized or invalid bytes are rejected at
+/// compile time. Implicit padding:
+///
+/// ```compile_fail
+/// # use sonic_test::zero_copy_deserialization::ZeroCopy;
+/// #[derive(ZeroCopy)]
+/// #[repr(C)]
+/// struct Entry {
+///     kind: u8,
+///     value: u64,
+/// }
+/// ```
+///
+/// A field that is not `Pod`:
+///
+/// ```compile_fail
+/// # use sonic_test::zero_copy_deserialization::ZeroCopy;
+/// #[derive(ZeroCopy)]
+/// #[repr(C)]
+/// struct Entry {
+///     kind: u8,
+///     active: bool,
+/// }
+/// ```
+///
+/// A layout the compiler may reorder:
+///
+/// ```compile_fail
+/// # use sonic_test::zero_copy_deserialization::ZeroCopy;
+/// #[derive(ZeroCopy)]
+/// struct Entry {
+///     kind: u8,
+///     value: [u8; 8],
+/// }
+/// ```
+///
+/// Generic structs, whose layout depends on the parameters:
+///
+/// ```compile_fail
+/// # use sonic_test::zero_copy_deserialization::ZeroCopy;
+/// #[derive(ZeroCopy)]
+/// #[repr(C)]
+/// struct Entry<T> {
+///     kind: u8,
+///     value: T,
+/// }
+/// ```
+pub use zero_copy_derive::ZeroCopy;
+
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod account_file;
+pub mod constraints;
+pub mod extensions;
+pub mod program_input;
+
+/// Parse failure with the byte offset where it happened.
+///
+/// Offsets are relative to the slice handed to the parser. Parsers that work
+/// on a sub-slice (for example `AccountIter`) rebase them with
+/// [`ParseError::offset_by`], so the offset always points into the caller's
+/// buffer.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum ParseError {
-    InsufficientData,
-    InvalidAlignment,
-    InvalidDataLength,
+    #[error("{field} needs {expected} bytes but only {actual} remain at offset {offset}")]
+    InsufficientData {
+        field: &'static str,
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("{field} at offset {offset} is not {align}-byte aligned")]
+    InvalidAlignment {
+        field: &'static str,
+        offset: usize,
+        align: usize,
+    },
+    #[error("{field} {length} exceeds remaining {remaining} bytes at offset {offset}")]
+    InvalidDataLength {
+        field: &'static str,
+        offset: usize,
+        length: usize,
+        remaining: usize,
+    },
+    #[error("{field} needs {required} bytes but capacity is {capacity} at offset {offset}")]
+    CapacityExceeded {
+        field: &'static str,
+        offset: usize,
+        required: usize,
+        capacity: usize,
+    },
+    #[error("invalid {field} at offset {offset}")]
+    InvalidValue { field: &'static str, offset: usize },
+    #[error("unknown discriminator {discriminator} at offset {offset}")]
+    UnknownDiscriminator { offset: usize, discriminator: u8 },
+    #[error("expected discriminator {expected} but found {actual} at offset {offset}")]
+    DiscriminatorMismatch { offset: usize, expected: u8, actual: u8 },
+    #[error("unknown layout version {version} at offset {offset}")]
+    UnknownVersion { offset: usize, version: u8 },
+    #[error("expected owner {expected} but found {actual} at offset {offset}")]
+    OwnerMismatch { offset: usize, expected: Pubkey, actual: Pubkey },
+    #[error("data length {length} is outside {min}..={max} at offset {offset}")]
+    DataLengthOutOfRange { offset: usize, length: usize, min: usize, max: usize },
+    #[error("amount must be non-zero at offset {offset}")]
+    ZeroAmount { offset: usize },
+}
+
+impl ParseError {
+    /// Byte offset of the failure.
+    pub fn offset(&self) -> usize {
+        match *self {
+            ParseError::InsufficientData { offset, .. }
+            | ParseError::InvalidAlignment { offset, .. }
+            | ParseError::InvalidDataLength { offset, .. }
+            | ParseError::CapacityExceeded { offset, .. }
+            | ParseError::InvalidValue { offset, .. }
+            | ParseError::UnknownDiscriminator { offset, .. }
+            | ParseError::DiscriminatorMismatch { offset, .. }
+            | ParseError::UnknownVersion { offset, .. }
+            | ParseError::OwnerMismatch { offset, .. }
+            | ParseError::DataLengthOutOfRange { offset, .. }
+            | ParseError::ZeroAmount { offset } => offset,
+        }
+    }
+
+    /// Shifts the offset by `base`, for errors from a parser that was given
+    /// `buffer[base..]`.
+    pub fn offset_by(mut self, base: usize) -> Self {
+        match &mut self {
+            ParseError::InsufficientData { offset, .. }
+            | ParseError::InvalidAlignment { offset, .. }
+            | ParseError::InvalidDataLength { offset, .. }
+            | ParseError::CapacityExceeded { offset, .. }
+            | ParseError::InvalidValue { offset, .. }
+            | ParseError::UnknownDiscriminator { offset, .. }
+            | ParseError::DiscriminatorMismatch { offset, .. }
+            | ParseError::UnknownVersion { offset, .. }
+            | ParseError::OwnerMismatch { offset, .. }
+            | ParseError::DataLengthOutOfRange { offset, .. }
+            | ParseError::ZeroAmount { offset } => *offset += base,
+        }
+        self
+    }
+
+    /// Annotated hexdump of `bytes` around the failing offset.
+    pub fn hexdump<'a>(&'a self, bytes: &'a [u8]) -> HexDump<'a> {
+        HexDump { bytes, error: self }
+    }
 }
 
+/// Renders the error message followed by the rows of the input around the
+/// failing offset, with a marker under the offending byte. Returned by
+/// [`ParseError::hexdump`].
+///
+/// ```text
+/// data_len 1000 exceeds remaining 5 bytes at offset 56
+/// 00000020  00 00 00 00 00 00 00 00  2a 00 00 00 00 00 00 00  |........*.......|
+/// 00000030  05 00 00 00 00 00 00 00  48 65 6c 6c 6f           |........Hello|
+///                                    ^^
+/// ```
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+    error: &'a ParseError,
+}
+
+impl HexDump<'_> {
+    const ROW: usize = 16;
+    const ROWS_BEFORE: usize = 2;
+    const ROWS_AFTER: usize = 1;
+
+    // Column of the hex digits for byte `i` of a row
+    fn column(i: usize) -> usize {
+        10 + i * 3 + usize::from(i >= 8)
+    }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.error)?;
+
+        // The offset can point one past the end, e.g. for truncated input
+        let offset = self.error.offset();
+        let marked_row = offset / Self::ROW;
+        let first_row = marked_row.saturating_sub(Self::ROWS_BEFORE);
+        let last_row = marked_row + Self::ROWS_AFTER;
+
+        for row in first_row..=last_row {
+            let start = row * Self::ROW;
+            let chunk = self.bytes.get(start..).unwrap_or(&[]);
+            let chunk = &chunk[..chunk.len().min(Self::ROW)];
+            if chunk.is_empty() && row != marked_row {
+                break;
+            }
+
+            write!(f, "{:08x}  ", start)?;
+            for i in 0..Self::ROW {
+                match chunk.get(i) {
+                    Some(byte) => write!(f, "{:02x} ", byte)?,
+                    None => f.write_str("   ")?,
+                }
+                if i == 7 {
+                    f.write_str(" ")?;
+                }
+            }
+            f.write_str(" |")?;
+            for &byte in chunk {
+                let c = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f, "|")?;
+
+            if row == marked_row {
+                let column = Self::column(offset % Self::ROW);
+                write!(f, "{:width$}^^", "", width = column)?;
+                if offset >= self.bytes.len() {
+                    f.write_str(" end of input")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Size and alignment checks behind the constructors generated by
+// `#[derive(ZeroCopy)]`.
+#[doc(hidden)]
+pub fn check_overlay<T>(bytes: &[u8], type_name: &'static str) -> Result<(), ParseError> {
+    if bytes.len() < mem::size_of::<T>() {
+        return Err(ParseError::InsufficientData {
+            field: type_name,
+            offset: 0,
+            expected: mem::size_of::<T>(),
+            actual: bytes.len(),
+        });
+    }
+    if !(bytes.as_ptr() as usize).is_multiple_of(mem::align_of::<T>()) {
+        return Err(ParseError::InvalidAlignment {
+            field: type_name,
+            offset: 0,
+            align: mem::align_of::<T>(),
+        });
+    }
+    Ok(())
+}
+
+/// Types that can be overlaid on arbitrary bytes: every bit pattern is a
+/// valid value and there is no padding. `#[derive(ZeroCopy)]` implements this
+/// for structs made only of `Pod` fields.
+///
+/// # Safety
+///
+/// Implementors must accept any bit pattern and contain no padding bytes.
+pub unsafe trait Pod {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for u128 {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for i64 {}
+unsafe impl Pod for i128 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Header layout versions, stored in [`AccountHeader::version`].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LayoutVersion {
+    /// The original 56-byte header.
+    V0 = 0,
+    /// Appends [`AccountHeaderV1`].
+    V1 = 1,
+}
+
+impl LayoutVersion {
+    /// Version written by [`AccountWriter`] and produced by migration.
+    pub const CURRENT: LayoutVersion = LayoutVersion::V1;
+
+    pub const fn from_u8(version: u8) -> Option<Self> {
+        match version {
+            0 => Some(LayoutVersion::V0),
+            1 => Some(LayoutVersion::V1),
+            _ => None,
+        }
+    }
+
+    /// Bytes from the start of the account to the start of its data.
+    pub const fn header_len(self) -> usize {
+        match self {
+            LayoutVersion::V0 => AccountHeader::SIZE,
+            LayoutVersion::V1 => AccountHeader::SIZE + AccountHeaderV1::SIZE,
+        }
+    }
+}
+
+// The padding `repr(C)` inserted when `amount` and `data_len` were native
+// integers is spelled out, so the byte layout is unchanged. Its first byte
+// now holds the layout version; older writers always zeroed it.
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, ZeroCopy)]
 pub struct AccountHeader {
     pub discriminator: u8,
-    pub owner: [u8; 32],
-    pub amount: u64,
-    pub data_len: u32,
+    pub owner: Pubkey,
+    pub version: u8,
+    _padding0: [u8; 6],
+    pub amount: LeU64,
+    pub data_len: LeU32,
+    _padding1: [u8; 4],
+}
+
+impl AccountHeader {
+    pub fn new(version: LayoutVersion, discriminator: u8, owner: Pubkey, amount: u64, data_len: u32) -> Self {
+        AccountHeader {
+            discriminator,
+            owner,
+            version: version as u8,
+            _padding0: [0; 6],
+            amount: LeU64::new(amount),
+            data_len: LeU32::new(data_len),
+            _padding1: [0; 4],
+        }
+    }
 }
 
+/// Fields added by [`LayoutVersion::V1`], stored right after the
+/// `AccountHeader`.
+#[repr(C)]
+#[derive(Debug, Default, ZeroCopy)]
+pub struct AccountHeaderV1 {
+    /// Slot the account was last written in.
+    pub slot: LeU64,
+}
+
+/// View over an account of any known layout version.
+///
+/// Accessors for fields that an account's version predates return their
+/// default value, so callers do not need to branch on the version.
 pub struct Account<'a> {
     pub header: &'a AccountHeader,
     pub data: &'a [u8],
+    v1: Option<&'a AccountHeaderV1>,
 }
 
+/// Former view for input at any address, now the same as [`Account`].
+#[deprecated(note = "`AccountHeader` has alignment 1; use `Account`")]
+pub type UnalignedAccount<'a> = Account<'a>;
+
+/// Former alignment-1 copy of the header, now the same as [`AccountHeader`].
+#[deprecated(note = "`AccountHeader` has alignment 1; use it directly")]
+pub type UnalignedAccountHeader = AccountHeader;
+
 impl<'a> Account<'a> {
-    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ParseError> {
-        // Check if we have enough data for the header
-        let header_size = mem::size_of::<AccountHeader>();
-        if bytes.len() < header_size {
-            return Err(ParseError::InsufficientData);
-        }
+    /// Parses input at any address. `from_bytes` does the same since the
+    /// header fields became alignment-1 wrappers.
+    #[deprecated(note = "`AccountHeader` has alignment 1; use `Account::from_bytes`")]
+    pub fn from_bytes_unaligned(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes(bytes)
+    }
 
-        // Check alignment - the AccountHeader should be aligned to its most restrictive field
-        // u64 requires 8-byte alignment
-        let ptr = bytes.as_ptr() as usize;
-        if ptr % 8 != 0 {
-            return Err(ParseError::InvalidAlignment);
-        }
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        let (version, data_len) = Self::validate(bytes)?;
 
-        // Unsafe block to perform zero-copy deserialization
-        // We've validated alignment and size, so this is safe
-        let header = unsafe {
-            &*(bytes.as_ptr() as *const AccountHeader)
+        // Zero-copy view of the header; the size was checked by the
+        // `ZeroCopy` constructor inside `validate`
+        let header = AccountHeader::from_bytes(bytes)?;
+        let v1 = match version {
+            LayoutVersion::V0 => None,
+            LayoutVersion::V1 => Some(AccountHeaderV1::from_bytes(&bytes[AccountHeader::SIZE..])?),
         };
 
+        // The data starts right after the versioned header
+        let data_start = version.header_len();
+        let data = &bytes[data_start..data_start + data_len];
+
+        Ok(Account { header, data, v1 })
+    }
+
+    // Checks that `bytes` can be viewed as a versioned header followed by
+    // its data and returns the version and data length. Shared by `Account`
+    // and `AccountMut` so both enforce the same rules.
+    fn validate(bytes: &[u8]) -> Result<(LayoutVersion, usize), ParseError> {
+        // The size check is generated by `#[derive(ZeroCopy)]`; the header
+        // has alignment 1, so any slice start is accepted
+        let header = AccountHeader::from_bytes(bytes)?;
+
+        let version = LayoutVersion::from_u8(header.version).ok_or(ParseError::UnknownVersion {
+            offset: AccountHeader::VERSION_OFFSET,
+            version: header.version,
+        })?;
+        if version >= LayoutVersion::V1 {
+            AccountHeaderV1::from_bytes(&bytes[AccountHeader::SIZE..]).map_err(|e| e.offset_by(AccountHeader::SIZE))?;
+        }
+
         // Validate the data length
-        let data_len = header.data_len as usize;
-        let expected_total_size = header_size + data_len;
-        
-        if bytes.len() < expected_total_size {
-            return Err(ParseError::InvalidDataLength);
+        let header_len = version.header_len();
+        let data_len = header.data_len.get() as usize;
+        let remaining = bytes.len() - header_len;
+        if data_len > remaining {
+            return Err(ParseError::InvalidDataLength {
+                field: "data_len",
+                offset: header_len,
+                length: data_len,
+                remaining,
+            });
         }
 
-        // Extract the data portion
-        // The data starts right after the header, but we need to account for struct padding
-        let data_start = mem::size_of::<AccountHeader>();
-        let data = &bytes[data_start..data_start + data_len];
-        
+        Ok((version, data_len))
+    }
+
+    pub fn version(&self) -> LayoutVersion {
+        LayoutVersion::from_u8(self.header.version).expect("validated in Account::from_bytes")
+    }
+
+    /// Bytes before the data, which depends on the layout version.
+    pub fn header_len(&self) -> usize {
+        self.version().header_len()
+    }
 
-        Ok(Account { header, data })
+    /// Bytes this account occupies in the input (header + data).
+    pub fn total_len(&self) -> usize {
+        self.header_len() + self.data.len()
     }
 
     pub fn discriminator(&self) -> u8 {
         self.header.discriminator
     }
 
-    pub fn owner(&self) -> &[u8; 32] {
+    pub fn owner(&self) -> &Pubkey {
         &self.header.owner
     }
 
     pub fn amount(&self) -> u64 {
-        self.header.amount
+        self.header.amount.get()
+    }
+
+    /// Slot of the last write; 0 for version 0 accounts.
+    pub fn slot(&self) -> u64 {
+        self.v1.map_or(0, |v1| v1.slot.get())
     }
 
     pub fn data(&self) -> &[u8] {
         self.data
     }
+
+    /// Re-encodes the account in the current layout into `buf`, for
+    /// buffers too small to migrate in place with [`AccountMut::migrate`].
+    /// Returns the number of bytes written.
+    pub fn migrate_into(&self, buf: &mut [u8]) -> Result<usize, ParseError> {
+        AccountWriter::from_account(self).version(LayoutVersion::CURRENT).write(buf)
+    }
+
+    /// Returns the typed view of the data section if the discriminator
+    /// matches `T`.
+    pub fn parse_as<T: AccountType>(&self) -> Result<T::View<'a>, ParseError> {
+        if self.discriminator() != T::DISCRIMINATOR {
+            return Err(ParseError::DiscriminatorMismatch {
+                offset: AccountHeader::DISCRIMINATOR_OFFSET,
+                expected: T::DISCRIMINATOR,
+                actual: self.discriminator(),
+            });
+        }
+        // Views report offsets within the data section
+        T::view(self.data).map_err(|e| e.offset_by(self.header_len()))
+    }
 }
 
-pub fn run_zero_copy_deserialization() {
-    println!("=== Zero-Copy Deserialization Example ===");
-    
-    // Create sample account data with proper alignment
-    let header_size = mem::size_of::<AccountHeader>();
-    let mut aligned_data = vec![0u8; header_size + 8]; // Extra space for alignment
-    
-    // Find the first 8-byte aligned position
-    let ptr = aligned_data.as_ptr() as usize;
-    let aligned_ptr = (ptr + 7) & !7; // Round up to next 8-byte boundary
-    let offset = aligned_ptr - ptr;
-    
-    // Create the header at the aligned position
-    let header = AccountHeader {
-        discriminator: 1,
-        owner: [0u8; 32],
-        amount: 42,
-        data_len: 5,
+/// An account kind identified by its discriminator byte.
+///
+/// `view` interprets the data section of an [`Account`] whose discriminator
+/// has already been checked against `DISCRIMINATOR`. Error offsets are
+/// relative to the start of `data`.
+pub trait AccountType {
+    const DISCRIMINATOR: u8;
+
+    type View<'a>;
+
+    fn view(data: &[u8]) -> Result<Self::View<'_>, ParseError>;
+}
+
+/// Declares an enum over several [`AccountType`]s, one variant per type,
+/// with `parse`/`from_bytes` constructors that dispatch on the discriminator
+/// byte and return `ParseError::UnknownDiscriminator` for anything not listed.
+/// See `run_zero_copy_deserialization` for an example.
+#[macro_export]
+macro_rules! account_registry {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident<$lt:lifetime> {
+            $($variant:ident($ty:ty)),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name<$lt> {
+            $($variant(<$ty as $crate::zero_copy_deserialization::AccountType>::View<$lt>)),+
+        }
+
+        impl<$lt> $name<$lt> {
+            pub fn parse(
+                account: &$crate::zero_copy_deserialization::Account<$lt>,
+            ) -> Result<Self, $crate::zero_copy_deserialization::ParseError> {
+                let discriminator = account.discriminator();
+                $(
+                    if discriminator == <$ty as $crate::zero_copy_deserialization::AccountType>::DISCRIMINATOR {
+                        return account.parse_as::<$ty>().map($name::$variant);
+                    }
+                )+
+                Err($crate::zero_copy_deserialization::ParseError::UnknownDiscriminator {
+                    offset: $crate::zero_copy_deserialization::AccountHeader::DISCRIMINATOR_OFFSET,
+                    discriminator,
+                })
+            }
+
+            pub fn from_bytes(bytes: &$lt [u8]) -> Result<Self, $crate::zero_copy_deserialization::ParseError> {
+                let account = $crate::zero_copy_deserialization::Account::from_bytes(bytes)?;
+                Self::parse(&account)
+            }
+        }
     };
-    
-    // Copy the header to the aligned position
-    unsafe {
-        std::ptr::copy_nonoverlapping(
-            &header as *const AccountHeader as *const u8,
-            aligned_data.as_mut_ptr().add(offset),
-            header_size
-        );
+}
+
+/// Mutable zero-copy view for updating an account in place.
+///
+/// The whole input slice is the account's capacity: the data region can grow
+/// up to `bytes.len()` minus the header length without reallocating.
+pub struct AccountMut<'a> {
+    bytes: &'a mut [u8],
+}
+
+impl<'a> AccountMut<'a> {
+    pub fn from_bytes(bytes: &'a mut [u8]) -> Result<Self, ParseError> {
+        Account::validate(bytes)?;
+        Ok(AccountMut { bytes })
     }
+
+    pub fn header(&self) -> &AccountHeader {
+        AccountHeader::from_bytes(self.bytes).expect("validated in AccountMut::from_bytes")
+    }
+
+    fn header_mut(&mut self) -> &mut AccountHeader {
+        AccountHeader::from_bytes_mut(self.bytes).expect("validated in AccountMut::from_bytes")
+    }
+
+    pub fn version(&self) -> LayoutVersion {
+        LayoutVersion::from_u8(self.header().version).expect("validated in AccountMut::from_bytes")
+    }
+
+    // Start of the data region
+    fn header_len(&self) -> usize {
+        self.version().header_len()
+    }
+
+    pub fn discriminator(&self) -> u8 {
+        self.header().discriminator
+    }
+
+    pub fn set_discriminator(&mut self, discriminator: u8) {
+        self.header_mut().discriminator = discriminator;
+    }
+
+    pub fn owner(&self) -> &Pubkey {
+        &self.header().owner
+    }
+
+    pub fn set_owner(&mut self, owner: Pubkey) {
+        self.header_mut().owner = owner;
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.header().amount.get()
+    }
+
+    pub fn set_amount(&mut self, amount: u64) {
+        self.header_mut().amount.set(amount);
+    }
+
+    pub fn data(&self) -> &[u8] {
+        let start = self.header_len();
+        &self.bytes[start..start + self.header().data_len.get() as usize]
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        let start = self.header_len();
+        let end = start + self.header().data_len.get() as usize;
+        &mut self.bytes[start..end]
+    }
+
+    /// Maximum data length the underlying buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.bytes.len() - self.header_len()
+    }
+
+    /// Changes the data length in place. Bytes exposed by growing are zeroed.
+    pub fn resize_data(&mut self, new_len: usize) -> Result<(), ParseError> {
+        let capacity = self.capacity().min(u32::MAX as usize);
+        if new_len > capacity {
+            return Err(ParseError::CapacityExceeded {
+                field: "data",
+                offset: self.header_len(),
+                required: new_len,
+                capacity,
+            });
+        }
+
+        let old_len = self.header().data_len.get() as usize;
+        if new_len > old_len {
+            let start = self.header_len();
+            self.bytes[start + old_len..start + new_len].fill(0);
+        }
+        self.header_mut().data_len.set(new_len as u32);
+        Ok(())
+    }
+
+    /// Rewrites the account in the current layout, moving the data to make
+    /// room for the fields added since its version, which are zeroed. Fails
+    /// without changing anything if the buffer cannot hold the larger header;
+    /// use [`Account::migrate_into`] to copy into a bigger buffer instead.
+    pub fn migrate(&mut self) -> Result<(), ParseError> {
+        let old_start = self.header_len();
+        let new_start = LayoutVersion::CURRENT.header_len();
+        if old_start == new_start {
+            return Ok(());
+        }
+
+        let data_len = self.header().data_len.get() as usize;
+        let required = new_start + data_len;
+        if required > self.bytes.len() {
+            return Err(ParseError::CapacityExceeded {
+                field: "account",
+                offset: 0,
+                required,
+                capacity: self.bytes.len(),
+            });
+        }
+
+        // Later versions only append header fields, so the data moves right
+        self.bytes.copy_within(old_start..old_start + data_len, new_start);
+        self.bytes[old_start..new_start].fill(0);
+        self.header_mut().version = LayoutVersion::CURRENT as u8;
+        Ok(())
+    }
+
+    /// Read-only view over the current contents.
+    pub fn as_account(&self) -> Account<'_> {
+        Account::from_bytes(self.bytes).expect("validated in AccountMut::from_bytes")
+    }
+}
+
+/// Records in a multi-account buffer start on this boundary, relative to
+/// the start of the buffer.
+pub const RECORD_ALIGNMENT: usize = 8;
+
+/// Bytes a record in the current layout with `data_len` bytes of data
+/// occupies in a multi-account buffer, including the padding before the next
+/// record.
+pub fn record_len(data_len: usize) -> usize {
+    (LayoutVersion::CURRENT.header_len() + data_len).next_multiple_of(RECORD_ALIGNMENT)
+}
+
+/// Walks `[header][data][padding]` records laid end to end, as found in
+/// snapshot files and append-vec style storage.
+///
+/// Yields each account together with its offset. Iteration stops after the
+/// first error, whose offset is relative to the start of the whole buffer.
+pub struct AccountIter<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> AccountIter<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        AccountIter { bytes, offset: 0, failed: false }
+    }
+
+    /// Offset of the next record to be parsed.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for AccountIter<'a> {
+    type Item = Result<(usize, Account<'a>), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset >= self.bytes.len() {
+            return None;
+        }
+
+        let offset = self.offset;
+        match Account::from_bytes(&self.bytes[offset..]) {
+            Ok(account) => {
+                // The last record may omit its trailing padding
+                let next = offset + account.total_len().next_multiple_of(RECORD_ALIGNMENT);
+                self.offset = next.min(self.bytes.len());
+                Some(Ok((offset, account)))
+            }
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error.offset_by(offset)))
+            }
+        }
+    }
+}
+
+/// The documented wire layout with no padding.
+#[repr(C)]
+#[derive(Debug, ZeroCopy)]
+pub struct PackedAccountHeader {
+    pub discriminator: u8,
+    pub owner: Pubkey,
+    pub amount: LeU64,
+    pub data_len: LeU32,
+}
+
+/// Size of the packed wire header: 1 + 32 + 8 + 4 bytes, no padding.
+pub const PACKED_HEADER_SIZE: usize = PackedAccountHeader::SIZE;
+pub const PACKED_DISCRIMINATOR_OFFSET: usize = PackedAccountHeader::DISCRIMINATOR_OFFSET;
+pub const PACKED_OWNER_OFFSET: usize = PackedAccountHeader::OWNER_OFFSET;
+pub const PACKED_AMOUNT_OFFSET: usize = PackedAccountHeader::AMOUNT_OFFSET;
+pub const PACKED_DATA_LEN_OFFSET: usize = PackedAccountHeader::DATA_LEN_OFFSET;
+
+const _: () = assert!(PACKED_HEADER_SIZE == 45);
+
+/// Zero-copy view over the packed wire layout.
+pub struct PackedAccount<'a> {
+    header: &'a PackedAccountHeader,
+    data: &'a [u8],
+}
+
+impl<'a> PackedAccount<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        let header = PackedAccountHeader::from_bytes(bytes)?;
+
+        let data_len = header.data_len.get() as usize;
+        let remaining = &bytes[PACKED_HEADER_SIZE..];
+        let data = remaining.get(..data_len).ok_or(ParseError::InvalidDataLength {
+            field: "data_len",
+            offset: PACKED_HEADER_SIZE,
+            length: data_len,
+            remaining: remaining.len(),
+        })?;
+
+        Ok(PackedAccount { header, data })
+    }
+
+    pub fn header(&self) -> &'a PackedAccountHeader {
+        self.header
+    }
+
+    pub fn discriminator(&self) -> u8 {
+        self.header.discriminator
+    }
+
+    pub fn owner(&self) -> &'a Pubkey {
+        &self.header.owner
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.header.amount.get()
+    }
+
+    pub fn data_len(&self) -> u32 {
+        self.header.data_len.get()
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Number of bytes this account occupies in the input (header + data).
+    pub fn total_len(&self) -> usize {
+        PACKED_HEADER_SIZE + self.data.len()
+    }
+}
+
+/// Allocation-free encoder for both account layouts.
+///
+/// The output buffer needs no particular alignment. Padding is always zeroed,
+/// which makes the output canonical: parsing a buffer and writing it back
+/// reproduces it exactly. New accounts use [`LayoutVersion::CURRENT`].
+#[derive(Debug, Clone, Copy)]
+pub struct AccountWriter<'d> {
+    version: LayoutVersion,
+    discriminator: u8,
+    owner: Pubkey,
+    amount: u64,
+    slot: u64,
+    data: &'d [u8],
+}
+
+impl Default for AccountWriter<'_> {
+    fn default() -> Self {
+        AccountWriter {
+            version: LayoutVersion::CURRENT,
+            discriminator: 0,
+            owner: Pubkey::default(),
+            amount: 0,
+            slot: 0,
+            data: &[],
+        }
+    }
+}
+
+impl<'d> AccountWriter<'d> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from the fields of a parsed account, keeping its version.
+    pub fn from_account(account: &Account<'d>) -> Self {
+        AccountWriter {
+            version: account.version(),
+            discriminator: account.discriminator(),
+            owner: *account.owner(),
+            amount: account.amount(),
+            slot: account.slot(),
+            data: account.data,
+        }
+    }
+
+    /// Starts from the fields of a parsed packed account.
+    pub fn from_packed(account: &PackedAccount<'d>) -> Self {
+        AccountWriter {
+            discriminator: account.discriminator(),
+            owner: *account.owner(),
+            amount: account.amount(),
+            data: account.data(),
+            ..Self::default()
+        }
+    }
+
+    /// Layout written by [`AccountWriter::write`]. Fields the version
+    /// predates, such as `slot` for `V0`, are dropped.
+    pub fn version(mut self, version: LayoutVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn discriminator(mut self, discriminator: u8) -> Self {
+        self.discriminator = discriminator;
+        self
+    }
+
+    pub fn owner(mut self, owner: Pubkey) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn amount(mut self, amount: u64) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    pub fn slot(mut self, slot: u64) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    pub fn data(mut self, data: &'d [u8]) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Bytes needed by [`AccountWriter::write`].
+    pub fn encoded_len(&self) -> usize {
+        self.version.header_len() + self.data.len()
+    }
+
+    /// Bytes needed by [`AccountWriter::write_packed`].
+    pub fn packed_len(&self) -> usize {
+        PACKED_HEADER_SIZE + self.data.len()
+    }
+
+    /// Encodes the `AccountHeader` layout parsed by [`Account::from_bytes`].
+    /// Returns the number of bytes written.
+    pub fn write(&self, buf: &mut [u8]) -> Result<usize, ParseError> {
+        let len = self.encoded_len();
+        let out = Self::output(buf, len)?;
+
+        let header_len = self.write_header(out, self.data.len())?;
+        out[header_len..].copy_from_slice(self.data);
+
+        Ok(len)
+    }
+
+    /// Encodes only the versioned header, declaring `data_len` bytes of data
+    /// (the writer's own data is ignored), for callers that fill the data in
+    /// place afterwards. Returns the header length.
+    pub fn write_header(&self, buf: &mut [u8], data_len: usize) -> Result<usize, ParseError> {
+        let data_len = Self::checked_data_len(data_len)?;
+        let len = self.version.header_len();
+        let out = Self::output(buf, len)?;
+
+        let (header, rest) = out.split_at_mut(AccountHeader::SIZE);
+        *AccountHeader::from_bytes_mut(header)? =
+            AccountHeader::new(self.version, self.discriminator, self.owner, self.amount, data_len);
+        if self.version >= LayoutVersion::V1 {
+            *AccountHeaderV1::from_bytes_mut(rest)? = AccountHeaderV1 { slot: LeU64::new(self.slot) };
+        }
+
+        Ok(len)
+    }
+
+    /// Encodes the packed wire layout parsed by [`PackedAccount::from_bytes`].
+    /// Returns the number of bytes written.
+    pub fn write_packed(&self, buf: &mut [u8]) -> Result<usize, ParseError> {
+        let data_len = Self::checked_data_len(self.data.len())?;
+        let len = self.packed_len();
+        let out = Self::output(buf, len)?;
+
+        let (header, data) = out.split_at_mut(PACKED_HEADER_SIZE);
+        *PackedAccountHeader::from_bytes_mut(header)? = PackedAccountHeader {
+            discriminator: self.discriminator,
+            owner: self.owner,
+            amount: LeU64::new(self.amount),
+            data_len: LeU32::new(data_len),
+        };
+        data.copy_from_slice(self.data);
+
+        Ok(len)
+    }
+
+    fn checked_data_len(data_len: usize) -> Result<u32, ParseError> {
+        u32::try_from(data_len).map_err(|_| ParseError::CapacityExceeded {
+            field: "data_len",
+            offset: 0,
+            required: data_len,
+            capacity: u32::MAX as usize,
+        })
+    }
+
+    fn output(buf: &mut [u8], len: usize) -> Result<&mut [u8], ParseError> {
+        let capacity = buf.len();
+        buf.get_mut(..len).ok_or(ParseError::CapacityExceeded {
+            field: "account",
+            offset: 0,
+            required: len,
+            capacity,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn run_zero_copy_deserialization() {
+    use crate::unsafe_rust_memory_management::AlignedBuffer;
+
+    println!("=== Zero-Copy Deserialization Example ===");
     
-    // Add some sample data right after the header
-    let sample_data = b"Hello";
-    let data_start = offset + header_size;
-    aligned_data.resize(data_start + sample_data.len(), 0);
-    aligned_data[data_start..data_start + sample_data.len()].copy_from_slice(sample_data);
-    
-    // Create a slice that starts at the aligned position and includes all the data
-    let account_data = &aligned_data[offset..];
+    // Describe the sample account
+    let header_size = LayoutVersion::CURRENT.header_len();
+    let writer = AccountWriter::new()
+        .discriminator(1)
+        .owner(Pubkey::default())
+        .amount(42)
+        .data(b"Hello");
+
+    // Create sample account data in an aligned allocation
+    let mut aligned_data = AlignedBuffer::new(writer.encoded_len(), RECORD_ALIGNMENT);
+    writer.write(&mut aligned_data).unwrap();
+    let account_data = &aligned_data[..];
     
-    println!("Created account data with {} bytes (aligned at offset {})", account_data.len(), offset);
+    println!(
+        "Created account data with {} bytes (aligned to {})",
+        account_data.len(),
+        aligned_data.align()
+    );
     
     // Parse the account using zero-copy deserialization
-    match Account::from_bytes(&account_data) {
+    match Account::from_bytes(account_data) {
         Ok(account) => {
             println!("Successfully parsed account:");
             println!("  Discriminator: {}", account.discriminator());
-            println!("  Owner: {:?}", account.owner());
+            println!("  Owner: {}", account.owner());
             println!("  Amount: {}", account.amount());
             println!("  Data: {:?}", String::from_utf8_lossy(account.data()));
         }
@@ -133,6 +1017,16 @@ pub fn run_zero_copy_deserialization() {
         }
     }
     
+    // Write the parsed account back out and compare
+    let mut reencoded = vec![0u8; account_data.len()];
+    let account = Account::from_bytes(account_data).unwrap();
+    AccountWriter::from_account(&account).write(&mut reencoded).unwrap();
+    if reencoded == account_data {
+        println!("✓ Parse followed by write reproduces the input byte for byte");
+    } else {
+        println!("Re-encoded account differs from the input");
+    }
+    
     // Test error cases
     println!("\n=== Testing Error Cases ===");
     
@@ -140,35 +1034,916 @@ pub fn run_zero_copy_deserialization() {
     let short_data = &account_data[..10];
     match Account::from_bytes(short_data) {
         Ok(_) => println!("Unexpected success with short data"),
-        Err(ParseError::InsufficientData) => println!("✓ Correctly detected insufficient data"),
+        Err(e @ ParseError::InsufficientData { .. }) => println!("✓ Correctly detected insufficient data: {}", e),
         Err(e) => println!("Unexpected error: {:?}", e),
     }
     
     // Test invalid data length - create a new buffer with invalid data_len
-    let mut invalid_aligned_data = vec![0u8; header_size + 8];
-    let invalid_ptr = invalid_aligned_data.as_ptr() as usize;
-    let invalid_aligned_ptr = (invalid_ptr + 7) & !7;
-    let invalid_offset = invalid_aligned_ptr - invalid_ptr;
-    
-    let invalid_header = AccountHeader {
-        discriminator: 1,
-        owner: [0u8; 32],
-        amount: 42,
-        data_len: 1000, // Invalid data length
-    };
+    let mut invalid_aligned_data = AlignedBuffer::new(header_size + 8, RECORD_ALIGNMENT);
     
-    unsafe {
-        std::ptr::copy_nonoverlapping(
-            &invalid_header as *const AccountHeader as *const u8,
-            invalid_aligned_data.as_mut_ptr().add(invalid_offset),
-            header_size
-        );
-    }
+    AccountWriter::new()
+        .discriminator(1)
+        .amount(42)
+        .write(&mut invalid_aligned_data)
+        .unwrap();
+
+    // Invalid data length
+    let data_len_offset = AccountHeader::DATA_LEN_OFFSET;
+    invalid_aligned_data[data_len_offset..data_len_offset + 4].copy_from_slice(&1000u32.to_le_bytes());
     
-    let invalid_account_data = &invalid_aligned_data[invalid_offset..];
+    let invalid_account_data = &invalid_aligned_data[..];
     match Account::from_bytes(invalid_account_data) {
         Ok(_) => println!("Unexpected success with invalid data length"),
-        Err(ParseError::InvalidDataLength) => println!("✓ Correctly detected invalid data length"),
+        Err(e @ ParseError::InvalidDataLength { .. }) => {
+            println!("✓ Correctly detected invalid data length:");
+            print!("{}", e.hexdump(invalid_account_data));
+        }
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+
+    // Dispatch on the discriminator byte
+    println!("\n=== Typed Dispatch ===");
+
+    struct Greeting;
+
+    impl AccountType for Greeting {
+        const DISCRIMINATOR: u8 = 1;
+        type View<'a> = &'a str;
+
+        fn view(data: &[u8]) -> Result<&str, ParseError> {
+            core::str::from_utf8(data).map_err(|e| ParseError::InvalidValue {
+                field: "greeting",
+                offset: e.valid_up_to(),
+            })
+        }
+    }
+
+    struct Counter;
+
+    impl AccountType for Counter {
+        const DISCRIMINATOR: u8 = 2;
+        type View<'a> = u64;
+
+        fn view(data: &[u8]) -> Result<u64, ParseError> {
+            let bytes = data.first_chunk::<8>().ok_or(ParseError::InsufficientData {
+                field: "counter",
+                offset: 0,
+                expected: 8,
+                actual: data.len(),
+            })?;
+            Ok(u64::from_le_bytes(*bytes))
+        }
+    }
+
+    account_registry! {
+        enum DemoAccount<'a> {
+            Greeting(Greeting),
+            Counter(Counter),
+        }
+    }
+
+    match DemoAccount::from_bytes(account_data) {
+        Ok(DemoAccount::Greeting(text)) => println!("✓ Dispatched discriminator 1 to Greeting: {:?}", text),
+        Ok(DemoAccount::Counter(value)) => println!("Unexpected Counter view: {}", value),
+        Err(e) => println!("Failed to dispatch account: {:?}", e),
+    }
+
+    match account.parse_as::<Counter>() {
+        Ok(_) => println!("Unexpected success parsing a Greeting as a Counter"),
+        Err(ParseError::DiscriminatorMismatch { expected, actual, .. }) => {
+            println!("✓ Correctly rejected Counter view (expected {}, found {})", expected, actual)
+        }
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+
+    let mut unknown = AlignedBuffer::new(account_data.len(), RECORD_ALIGNMENT);
+    writer.discriminator(9).write(&mut unknown).unwrap();
+    match DemoAccount::from_bytes(&unknown) {
+        Ok(_) => println!("Unexpected success with unknown discriminator"),
+        Err(ParseError::UnknownDiscriminator { discriminator, .. }) => {
+            println!("✓ Correctly rejected unknown discriminator {}", discriminator)
+        }
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+
+    // Check owner, type and size while parsing
+    println!("\n=== Checked Parsing ===");
+
+    use constraints::AccountConstraints;
+
+    let owner = Pubkey::default();
+    match Account::from_bytes_checked(account_data, &owner, Greeting::DISCRIMINATOR) {
+        Ok(account) => println!("✓ Accepted greeting owned by {}", account.owner()),
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+
+    let other_program = Pubkey::new([7u8; 32]);
+    match Account::from_bytes_checked(account_data, &other_program, Greeting::DISCRIMINATOR) {
+        Ok(_) => println!("Unexpected success with the wrong owner"),
+        Err(e @ ParseError::OwnerMismatch { .. }) => println!("✓ Correctly rejected wrong owner: {}", e),
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+
+    const COUNTER: AccountConstraints = AccountConstraints::new()
+        .owner(Pubkey::new([0u8; 32]))
+        .discriminator(Counter::DISCRIMINATOR)
+        .min_data_len(8)
+        .max_data_len(8)
+        .non_zero_amount();
+
+    let mut counter = [0u8; 72];
+    writer.discriminator(Counter::DISCRIMINATOR).data(&[0; 4]).write(&mut counter).unwrap();
+    match Account::from_bytes_with(&counter, &COUNTER) {
+        Ok(_) => println!("Unexpected success with a short counter"),
+        Err(e @ ParseError::DataLengthOutOfRange { .. }) => println!("✓ Correctly rejected short counter: {}", e),
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+    writer.discriminator(Counter::DISCRIMINATOR).amount(0).data(&[0; 8]).write(&mut counter).unwrap();
+    match Account::from_bytes_with(&counter, &COUNTER) {
+        Ok(_) => println!("Unexpected success with an empty counter"),
+        Err(e @ ParseError::ZeroAmount { .. }) => println!("✓ Correctly rejected empty counter: {}", e),
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+
+    // Walk several records stored back to back
+    println!("\n=== Multi-Account Buffer ===");
+
+    let payloads: [&[u8]; 3] = [b"first", b"second record", b""];
+    let total: usize = payloads.iter().map(|p| record_len(p.len())).sum();
+    let mut records = AlignedBuffer::new(total, RECORD_ALIGNMENT);
+
+    let mut cursor = 0;
+    for (i, payload) in payloads.iter().enumerate() {
+        writer.discriminator(i as u8).amount(i as u64 * 100).data(payload).write(&mut records[cursor..]).unwrap();
+        cursor += record_len(payload.len());
+    }
+
+    for record in AccountIter::new(&records) {
+        match record {
+            Ok((offset, account)) => println!(
+                "  offset {:>3}: discriminator {}, amount {}, data {:?}",
+                offset,
+                account.discriminator(),
+                account.amount(),
+                String::from_utf8_lossy(account.data())
+            ),
+            Err(e) => println!("Failed to parse record: {:?}", e),
+        }
+    }
+
+    // Cut the buffer in the middle of the second record
+    let truncated = &records[..record_len(payloads[0].len()) + 20];
+    match AccountIter::new(truncated).find_map(Result::err) {
+        Some(error) => println!("✓ Stopped at truncated record: {}", error),
+        None => println!("Unexpected success with truncated buffer"),
+    }
+
+    // Update an account in place
+    println!("\n=== In-Place Updates ===");
+
+    let mut writable = AlignedBuffer::new(account_data.len() + 16, RECORD_ALIGNMENT);
+    writable[..account_data.len()].copy_from_slice(account_data);
+
+    match AccountMut::from_bytes(&mut writable) {
+        Ok(mut account) => {
+            account.set_amount(1_000);
+            account.data_mut().copy_from_slice(b"HELLO");
+            account.resize_data(11).unwrap();
+            account.data_mut()[5..].copy_from_slice(b" WORLD");
+            println!("Updated account (capacity {} bytes):", account.capacity());
+            println!("  Amount: {}", account.amount());
+            println!("  Data: {:?}", String::from_utf8_lossy(account.data()));
+
+            match account.resize_data(account.capacity() + 1) {
+                Ok(()) => println!("Unexpected success growing past capacity"),
+                Err(e @ ParseError::CapacityExceeded { .. }) => println!("✓ Correctly refused to grow past capacity: {}", e),
+                Err(e) => println!("Unexpected error: {:?}", e),
+            }
+        }
+        Err(e) => println!("Failed to open account for writing: {:?}", e),
+    }
+
+    // Type-length-value extensions after an 8-byte base layout
+    println!("\n=== TLV Extensions ===");
+
+    const BASE_LEN: usize = 8;
+    const MEMO: u16 = 1;
+    const FEE: u16 = 2;
+
+    let base = 7u64.to_le_bytes();
+    let mut tlv = vec![0u8; writer.data(&base).encoded_len() + 64];
+    writer.data(&base).write(&mut tlv).unwrap();
+
+    let mut account = AccountMut::from_bytes(&mut tlv).unwrap();
+    account.set_extension(BASE_LEN, MEMO, b"hi").unwrap();
+    account.set_extension(BASE_LEN, FEE, &250u16.to_le_bytes()).unwrap();
+    // Growing the first extension moves the second one along
+    account.set_extension(BASE_LEN, MEMO, b"hello there").unwrap();
+    account.extension_mut(BASE_LEN, FEE).unwrap().unwrap().copy_from_slice(&300u16.to_le_bytes());
+
+    let account = account.as_account();
+    for entry in account.extensions(BASE_LEN) {
+        match entry {
+            Ok(entry) => println!(
+                "  offset {:>3}: type {}, value {:?}",
+                entry.offset, entry.extension_type, entry.value
+            ),
+            Err(e) => println!("Failed to parse extension: {:?}", e),
+        }
+    }
+    match account.extension(BASE_LEN, FEE) {
+        Ok(Some(fee)) => println!("✓ Looked up fee extension: {}", u16::from_le_bytes([fee[0], fee[1]])),
+        Ok(None) => println!("Fee extension is missing"),
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+
+    // Claim more bytes than the second entry has
+    let second = account.extensions(BASE_LEN).nth(1).unwrap().unwrap().offset;
+    let data_len = account.total_len();
+    let mut corrupt = tlv[..data_len].to_vec();
+    corrupt[second + 2..second + 4].copy_from_slice(&40u16.to_le_bytes());
+    let corrupt_account = Account::from_bytes(&corrupt).unwrap();
+    match corrupt_account.extension(BASE_LEN, FEE) {
+        Ok(_) => println!("Unexpected success with corrupt extension"),
+        Err(e @ ParseError::InvalidDataLength { .. }) => {
+            println!("✓ Correctly rejected corrupt extension:");
+            print!("{}", e.hexdump(&corrupt));
+        }
         Err(e) => println!("Unexpected error: {:?}", e),
     }
-}
\ No newline at end of file
+
+    // Read and migrate an account written before the header was versioned
+    println!("\n=== Layout Versions ===");
+
+    let legacy = writer.version(LayoutVersion::V0).slot(99);
+    let mut migratable = vec![0u8; LayoutVersion::CURRENT.header_len() + 5];
+    let legacy_len = legacy.write(&mut migratable).unwrap();
+
+    let account = Account::from_bytes(&migratable[..legacy_len]).unwrap();
+    println!(
+        "Legacy account: version {:?}, {} byte header, slot {}, data {:?}",
+        account.version(),
+        account.header_len(),
+        account.slot(),
+        String::from_utf8_lossy(account.data())
+    );
+
+    // Without spare capacity the account has to be copied out
+    let mut tight = migratable[..legacy_len].to_vec();
+    match AccountMut::from_bytes(&mut tight).unwrap().migrate() {
+        Ok(()) => println!("Unexpected success migrating without capacity"),
+        Err(e @ ParseError::CapacityExceeded { .. }) => println!("✓ Correctly refused to migrate in place: {}", e),
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+    let mut migrated = vec![0u8; LayoutVersion::CURRENT.header_len() + 5];
+    Account::from_bytes(&tight).unwrap().migrate_into(&mut migrated).unwrap();
+
+    let mut account = AccountMut::from_bytes(&mut migratable).unwrap();
+    account.migrate().unwrap();
+    let account = account.as_account();
+    println!(
+        "Migrated in place: version {:?}, {} byte header, slot {}, data {:?}",
+        account.version(),
+        account.header_len(),
+        account.slot(),
+        String::from_utf8_lossy(account.data())
+    );
+    if migratable == migrated {
+        println!("✓ In-place migration matches copying into a new buffer");
+    } else {
+        println!("In-place migration differs from copying into a new buffer");
+    }
+
+    migratable[AccountHeader::VERSION_OFFSET] = 7;
+    match Account::from_bytes(&migratable) {
+        Ok(_) => println!("Unexpected success with unknown version"),
+        Err(e @ ParseError::UnknownVersion { .. }) => println!("✓ Correctly rejected unknown version: {}", e),
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+
+    // Parse the same bytes from a misaligned slice
+    println!("\n=== Unaligned Parsing ===");
+
+    let mut shifted = vec![0u8; account_data.len() + 1];
+    shifted[1..].copy_from_slice(account_data);
+    let misaligned = &shifted[1..];
+
+    match Account::from_bytes(misaligned) {
+        Ok(account) => println!(
+            "✓ Parsed misaligned slice: amount {} and data {:?}",
+            account.amount(),
+            String::from_utf8_lossy(account.data())
+        ),
+        Err(e) => println!("Failed to parse misaligned account: {:?}", e),
+    }
+
+    // Parse the packed 45-byte wire layout
+    println!("\n=== Packed Wire Layout ===");
+
+    let mut packed = [0u8; PACKED_HEADER_SIZE + 5];
+    writer.owner(Pubkey::new([7u8; 32])).write_packed(&mut packed).unwrap();
+
+    // Parse from an odd offset to show that alignment does not matter here
+    let mut unaligned = vec![0u8; packed.len() + 1];
+    unaligned[1..].copy_from_slice(&packed);
+
+    match PackedAccount::from_bytes(&unaligned[1..]) {
+        Ok(account) => {
+            println!("Successfully parsed packed account ({} bytes):", account.total_len());
+            println!("  Discriminator: {}", account.discriminator());
+            println!("  Owner: {}", account.owner());
+            println!("  Amount: {}", account.amount());
+            println!("  Data: {:?}", String::from_utf8_lossy(account.data()));
+        }
+        Err(e) => {
+            println!("Failed to parse packed account: {:?}", e);
+        }
+    }
+
+    let mut packed_reencoded = [0u8; PACKED_HEADER_SIZE + 5];
+    let packed_account = PackedAccount::from_bytes(&packed).unwrap();
+    AccountWriter::from_packed(&packed_account).write_packed(&mut packed_reencoded).unwrap();
+    if packed_reencoded == packed {
+        println!("✓ Packed parse followed by write reproduces the input byte for byte");
+    } else {
+        println!("Re-encoded packed account differs from the input");
+    }
+
+    match PackedAccount::from_bytes(&packed[..PACKED_HEADER_SIZE + 2]) {
+        Ok(_) => println!("Unexpected success with truncated packed data"),
+        Err(e @ ParseError::InvalidDataLength { .. }) => println!("✓ Correctly detected truncated packed data: {}", e),
+        Err(e) => println!("Unexpected error: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A packed account spelled out byte by byte
+    fn packed_bytes(data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![7u8];
+        bytes.extend_from_slice(&[0x11; 32]);
+        bytes.extend_from_slice(&0x0102_0304_0506_0708u64.to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn packed_layout_has_no_padding() {
+        assert_eq!(PACKED_HEADER_SIZE, 45);
+        assert_eq!(mem::align_of::<PackedAccountHeader>(), 1);
+        assert_eq!(
+            [PACKED_DISCRIMINATOR_OFFSET, PACKED_OWNER_OFFSET, PACKED_AMOUNT_OFFSET, PACKED_DATA_LEN_OFFSET],
+            [0, 1, 33, 41]
+        );
+    }
+
+    #[test]
+    fn packed_account_parses_at_any_offset() {
+        let bytes = packed_bytes(b"Hello");
+        for shift in 0..8 {
+            let mut shifted = vec![0xEE; shift];
+            shifted.extend_from_slice(&bytes);
+            // Trailing bytes after the data are not part of the account
+            shifted.extend_from_slice(b"next");
+
+            let account = PackedAccount::from_bytes(&shifted[shift..]).unwrap();
+            assert_eq!(account.discriminator(), 7);
+            assert_eq!(account.owner(), &Pubkey::new([0x11; 32]));
+            assert_eq!(account.amount(), 0x0102_0304_0506_0708);
+            assert_eq!(account.data_len(), 5);
+            assert_eq!(account.data(), b"Hello");
+            assert_eq!(account.total_len(), PACKED_HEADER_SIZE + 5);
+        }
+    }
+
+    #[test]
+    fn packed_account_rejects_short_input() {
+        let bytes = packed_bytes(b"Hello");
+        assert_eq!(
+            PackedAccount::from_bytes(&bytes[..PACKED_HEADER_SIZE - 1]).err().unwrap(),
+            ParseError::InsufficientData {
+                field: "PackedAccountHeader",
+                offset: 0,
+                expected: PACKED_HEADER_SIZE,
+                actual: PACKED_HEADER_SIZE - 1,
+            }
+        );
+        assert_eq!(
+            PackedAccount::from_bytes(&bytes[..PACKED_HEADER_SIZE + 2]).err().unwrap(),
+            ParseError::InvalidDataLength {
+                field: "data_len",
+                offset: PACKED_HEADER_SIZE,
+                length: 5,
+                remaining: 2,
+            }
+        );
+    }
+
+    // The account the tests below start from: discriminator 1, owner
+    // [2; 32] and amount 42
+    pub(super) fn sample(data: &[u8]) -> AccountWriter<'_> {
+        AccountWriter::new().discriminator(1).owner(Pubkey::new([2; 32])).amount(42).data(data)
+    }
+
+    // `writer`'s account followed by `spare` bytes of capacity
+    pub(super) fn account_bytes(writer: AccountWriter<'_>, spare: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; writer.encoded_len() + spare];
+        writer.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn unaligned_parser_is_an_alias_of_from_bytes() {
+        let mut shifted = vec![0u8];
+        shifted.extend(account_bytes(sample(b"Hello"), 0));
+
+        let account: UnalignedAccount<'_> = Account::from_bytes_unaligned(&shifted[1..]).unwrap();
+        let header: &UnalignedAccountHeader = account.header;
+        assert_eq!(header.amount.get(), 42);
+        assert_eq!(account.data(), b"Hello");
+        assert_eq!(
+            Account::from_bytes_unaligned(&shifted[1..10]).err(),
+            Account::from_bytes(&shifted[1..10]).err()
+        );
+    }
+
+    #[test]
+    fn account_mut_setters_write_the_header() {
+        let mut bytes = account_bytes(sample(b"Hello"), 0);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        account.set_discriminator(9);
+        account.set_owner(Pubkey::new([5; 32]));
+        account.set_amount(0x0102_0304_0506_0708);
+        account.data_mut().copy_from_slice(b"HELLO");
+
+        assert_eq!(account.discriminator(), 9);
+        assert_eq!(account.owner(), &Pubkey::new([5; 32]));
+        assert_eq!(account.amount(), 0x0102_0304_0506_0708);
+        assert_eq!(account.data(), b"HELLO");
+
+        // Integers are little-endian at their header offsets
+        let amount = AccountHeader::AMOUNT_OFFSET;
+        assert_eq!(bytes[amount..amount + 8], [8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(bytes[AccountHeader::DISCRIMINATOR_OFFSET], 9);
+        assert_eq!(bytes[AccountHeader::OWNER_OFFSET..AccountHeader::OWNER_OFFSET + 32], [5; 32]);
+        let account = Account::from_bytes(&bytes).unwrap();
+        assert_eq!((account.amount(), account.data()), (0x0102_0304_0506_0708, &b"HELLO"[..]));
+    }
+
+    #[test]
+    fn resize_data_zeroes_grown_bytes() {
+        let mut bytes = account_bytes(sample(b"Hello"), 8);
+        let header_len = LayoutVersion::CURRENT.header_len();
+        // Stale bytes past the data must not reappear
+        bytes[header_len + 5..].fill(0xFF);
+
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        assert_eq!(account.capacity(), 13);
+        account.resize_data(2).unwrap();
+        assert_eq!(account.data(), b"He");
+        account.resize_data(13).unwrap();
+        assert_eq!(account.data(), b"He\0\0\0\0\0\0\0\0\0\0\0");
+        assert_eq!(bytes[AccountHeader::DATA_LEN_OFFSET..AccountHeader::DATA_LEN_OFFSET + 4], [13, 0, 0, 0]);
+    }
+
+    #[test]
+    fn resize_data_stops_at_capacity() {
+        let mut bytes = account_bytes(sample(b"Hello"), 3);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+
+        assert_eq!(
+            account.resize_data(9),
+            Err(ParseError::CapacityExceeded {
+                field: "data",
+                offset: LayoutVersion::CURRENT.header_len(),
+                required: 9,
+                capacity: 8,
+            })
+        );
+        assert_eq!(account.data(), b"Hello");
+        assert_eq!(account.resize_data(8), Ok(()));
+        assert_eq!(account.resize_data(0), Ok(()));
+        assert!(account.data().is_empty());
+    }
+
+    #[test]
+    fn account_mut_validates_like_account() {
+        let mut bytes = account_bytes(sample(b"Hello"), 0);
+        let len = bytes.len();
+        assert_eq!(
+            AccountMut::from_bytes(&mut bytes[..len - 1]).err().unwrap(),
+            ParseError::InvalidDataLength {
+                field: "data_len",
+                offset: LayoutVersion::CURRENT.header_len(),
+                length: 5,
+                remaining: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn writer_output_is_canonical() {
+        let writer = AccountWriter::new()
+            .discriminator(3)
+            .owner(Pubkey::new([4; 32]))
+            .amount(0x0102_0304_0506_0708)
+            .slot(0x1122)
+            .data(b"abc");
+
+        let mut expected = vec![3u8];
+        expected.extend_from_slice(&[4; 32]);
+        expected.push(LayoutVersion::V1 as u8);
+        expected.extend_from_slice(&[0; 6]);
+        expected.extend_from_slice(&[8, 7, 6, 5, 4, 3, 2, 1]);
+        expected.extend_from_slice(&[3, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[0x22, 0x11, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(b"abc");
+
+        // Padding is zeroed whatever the buffer held before
+        let mut buf = vec![0xFF; writer.encoded_len() + 2];
+        assert_eq!(writer.write(&mut buf), Ok(expected.len()));
+        assert_eq!(writer.encoded_len(), expected.len());
+        assert_eq!(buf[..expected.len()], expected[..]);
+        assert_eq!(buf[expected.len()..], [0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn writer_round_trips_both_layouts() {
+        let bytes = account_bytes(sample(b"payload"), 0);
+        let account = Account::from_bytes(&bytes).unwrap();
+        let mut out = vec![0u8; bytes.len()];
+        AccountWriter::from_account(&account).write(&mut out).unwrap();
+        assert_eq!(out, bytes);
+
+        let writer = AccountWriter::from_account(&account);
+        let mut packed = vec![0u8; writer.packed_len()];
+        assert_eq!(writer.write_packed(&mut packed), Ok(PACKED_HEADER_SIZE + 7));
+        let packed_account = PackedAccount::from_bytes(&packed).unwrap();
+        assert_eq!((packed_account.amount(), packed_account.data()), (42, &b"payload"[..]));
+        let mut repacked = vec![0u8; packed.len()];
+        AccountWriter::from_packed(&packed_account).write_packed(&mut repacked).unwrap();
+        assert_eq!(repacked, packed);
+    }
+
+    #[test]
+    fn writer_drops_fields_the_version_predates() {
+        let writer = AccountWriter::new().version(LayoutVersion::V0).slot(99).data(b"x");
+        let mut buf = [0u8; 64];
+        assert_eq!(writer.write(&mut buf), Ok(AccountHeader::SIZE + 1));
+
+        let account = Account::from_bytes(&buf[..AccountHeader::SIZE + 1]).unwrap();
+        assert_eq!(account.version(), LayoutVersion::V0);
+        assert_eq!(account.slot(), 0);
+        assert_eq!(account.data(), b"x");
+    }
+
+    #[test]
+    fn writer_reports_short_buffers_without_writing() {
+        let writer = AccountWriter::new().amount(1).data(b"abc");
+        let mut buf = vec![0xFF; writer.encoded_len() - 1];
+        assert_eq!(
+            writer.write(&mut buf),
+            Err(ParseError::CapacityExceeded {
+                field: "account",
+                offset: 0,
+                required: writer.encoded_len(),
+                capacity: buf.len(),
+            })
+        );
+        assert!(buf.iter().all(|&b| b == 0xFF));
+
+        let mut packed = vec![0u8; writer.packed_len() - 1];
+        assert!(matches!(writer.write_packed(&mut packed), Err(ParseError::CapacityExceeded { .. })));
+    }
+
+    #[test]
+    fn write_header_leaves_the_data_alone() {
+        let writer = AccountWriter::new().amount(5).data(b"ignored");
+        let header_len = LayoutVersion::CURRENT.header_len();
+        let mut buf = vec![0xAB; header_len + 4];
+        assert_eq!(writer.write_header(&mut buf, 4), Ok(header_len));
+
+        let account = Account::from_bytes(&buf).unwrap();
+        assert_eq!((account.amount(), account.data()), (5, &[0xAB; 4][..]));
+    }
+
+    struct Text;
+
+    impl AccountType for Text {
+        const DISCRIMINATOR: u8 = 1;
+        type View<'a> = &'a [u8];
+
+        fn view(data: &[u8]) -> Result<&[u8], ParseError> {
+            Ok(data)
+        }
+    }
+
+    struct Counter;
+
+    impl AccountType for Counter {
+        const DISCRIMINATOR: u8 = 2;
+        type View<'a> = u64;
+
+        fn view(data: &[u8]) -> Result<u64, ParseError> {
+            let bytes = data.first_chunk::<8>().ok_or(ParseError::InsufficientData {
+                field: "counter",
+                offset: 0,
+                expected: 8,
+                actual: data.len(),
+            })?;
+            Ok(u64::from_le_bytes(*bytes))
+        }
+    }
+
+    crate::account_registry! {
+        enum TestAccount<'a> {
+            Text(Text),
+            Counter(Counter),
+        }
+    }
+
+    #[test]
+    fn registry_dispatches_on_the_discriminator() {
+        let text = account_bytes(sample(b"hi"), 0);
+        assert!(matches!(TestAccount::from_bytes(&text), Ok(TestAccount::Text(b"hi"))));
+
+        let count = 7u64.to_le_bytes();
+        let counter = account_bytes(sample(&count).discriminator(2), 0);
+        assert!(matches!(TestAccount::from_bytes(&counter), Ok(TestAccount::Counter(7))));
+
+        let account = Account::from_bytes(&counter).unwrap();
+        assert!(matches!(TestAccount::parse(&account), Ok(TestAccount::Counter(7))));
+    }
+
+    #[test]
+    fn registry_rejects_unknown_discriminators() {
+        let bytes = account_bytes(sample(b"hi").discriminator(9), 0);
+        assert_eq!(
+            TestAccount::from_bytes(&bytes).err().unwrap(),
+            ParseError::UnknownDiscriminator { offset: AccountHeader::DISCRIMINATOR_OFFSET, discriminator: 9 }
+        );
+    }
+
+    #[test]
+    fn registry_reports_view_errors_at_account_offsets() {
+        // Too short for a counter; the offset points at the data
+        let bytes = account_bytes(sample(&[1, 2, 3]).discriminator(2), 0);
+        assert_eq!(
+            TestAccount::from_bytes(&bytes).err().unwrap(),
+            ParseError::InsufficientData {
+                field: "counter",
+                offset: LayoutVersion::CURRENT.header_len(),
+                expected: 8,
+                actual: 3,
+            }
+        );
+
+        // Layout errors come before dispatch
+        assert!(matches!(
+            TestAccount::from_bytes(&bytes[..10]).err().unwrap(),
+            ParseError::InsufficientData { field: "AccountHeader", .. }
+        ));
+    }
+
+    #[test]
+    fn parse_as_checks_the_discriminator() {
+        let bytes = account_bytes(sample(b"hi"), 0);
+        let account = Account::from_bytes(&bytes).unwrap();
+        assert_eq!(account.parse_as::<Text>(), Ok(&b"hi"[..]));
+        assert_eq!(
+            account.parse_as::<Counter>(),
+            Err(ParseError::DiscriminatorMismatch {
+                offset: AccountHeader::DISCRIMINATOR_OFFSET,
+                expected: 2,
+                actual: 1,
+            })
+        );
+    }
+
+    // Records with these payloads written back to back, with their offsets
+    fn records(payloads: &[&[u8]]) -> (Vec<u8>, Vec<usize>) {
+        let mut bytes = Vec::new();
+        let mut offsets = Vec::new();
+        for (i, payload) in payloads.iter().enumerate() {
+            let record = sample(payload).amount(i as u64);
+            offsets.push(bytes.len());
+            bytes.extend(account_bytes(record, record_len(payload.len()) - record.encoded_len()));
+        }
+        (bytes, offsets)
+    }
+
+    #[test]
+    fn records_start_on_the_record_alignment() {
+        let header_len = LayoutVersion::CURRENT.header_len();
+        assert_eq!(record_len(0), header_len);
+        assert_eq!(record_len(1), header_len + RECORD_ALIGNMENT);
+        assert_eq!(record_len(RECORD_ALIGNMENT), header_len + RECORD_ALIGNMENT);
+
+        let (bytes, offsets) = records(&[b"first", b"second record", b""]);
+        assert_eq!(offsets, [0, header_len + 8, 2 * header_len + 24]);
+
+        let parsed: Vec<_> = AccountIter::new(&bytes).map(Result::unwrap).collect();
+        assert_eq!(parsed.len(), 3);
+        for (i, (offset, account)) in parsed.iter().enumerate() {
+            assert_eq!(*offset, offsets[i]);
+            assert_eq!(offset % RECORD_ALIGNMENT, 0);
+            assert_eq!(account.amount(), i as u64);
+        }
+        assert_eq!(parsed[1].1.data(), b"second record");
+    }
+
+    #[test]
+    fn aligned_buffer_feeds_the_parser() {
+        use crate::unsafe_rust_memory_management::AlignedBuffer;
+
+        let (bytes, offsets) = records(&[b"first", b"second record", b""]);
+        let mut buffer = AlignedBuffer::new(bytes.len(), RECORD_ALIGNMENT);
+        buffer.copy_from_slice(&bytes);
+
+        let parsed: Vec<_> = AccountIter::new(&buffer).map(Result::unwrap).collect();
+        assert_eq!(parsed.iter().map(|(offset, _)| *offset).collect::<Vec<_>>(), offsets);
+        for (offset, _) in &parsed {
+            assert!((buffer[*offset..].as_ptr() as usize).is_multiple_of(RECORD_ALIGNMENT));
+        }
+        assert_eq!(parsed[1].1.data(), b"second record");
+
+        // Writers fill the buffer in place
+        let writer = sample(b"in place").amount(7);
+        let mut buffer = AlignedBuffer::new(writer.encoded_len(), RECORD_ALIGNMENT);
+        writer.write(&mut buffer).unwrap();
+        let account = Account::from_bytes(&buffer).unwrap();
+        assert_eq!((account.amount(), account.data()), (7, &b"in place"[..]));
+    }
+
+    #[test]
+    fn last_record_may_omit_its_padding() {
+        let (bytes, offsets) = records(&[b"first", b"last"]);
+        let end = offsets[1] + LayoutVersion::CURRENT.header_len() + 4;
+
+        let mut iter = AccountIter::new(&bytes[..end]);
+        assert_eq!(iter.next().unwrap().unwrap().0, 0);
+        assert_eq!(iter.offset(), offsets[1]);
+        assert_eq!(iter.next().unwrap().unwrap().1.data(), b"last");
+        assert_eq!(iter.offset(), end);
+        assert!(iter.next().is_none());
+        assert!(AccountIter::new(&[]).next().is_none());
+    }
+
+    #[test]
+    fn truncated_records_report_buffer_offsets() {
+        let (bytes, offsets) = records(&[b"first", b"second record"]);
+        let second = offsets[1];
+
+        // Cut inside the second header
+        let mut iter = AccountIter::new(&bytes[..second + 20]);
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(
+            iter.next().unwrap().err().unwrap(),
+            ParseError::InsufficientData { field: "AccountHeader", offset: second, expected: AccountHeader::SIZE, actual: 20 }
+        );
+        // Iteration stops after the first error
+        assert!(iter.next().is_none());
+
+        // Cut inside the second record's data
+        let data_start = second + LayoutVersion::CURRENT.header_len();
+        let error = AccountIter::new(&bytes[..data_start + 3]).find_map(Result::err).unwrap();
+        assert_eq!(
+            error,
+            ParseError::InvalidDataLength { field: "data_len", offset: data_start, length: 13, remaining: 3 }
+        );
+    }
+
+    // Printable bytes 0x20..0x48, three rows with a partial last row
+    fn printable() -> [u8; 40] {
+        core::array::from_fn(|i| 0x20 + i as u8)
+    }
+
+    #[test]
+    fn hexdump_marks_the_failing_byte() {
+        let error = ParseError::InvalidValue { field: "flag", offset: 36 };
+        let expected = concat!(
+            "invalid flag at offset 36\n",
+            "00000000  20 21 22 23 24 25 26 27  28 29 2a 2b 2c 2d 2e 2f  | !\"#$%&'()*+,-./|\n",
+            "00000010  30 31 32 33 34 35 36 37  38 39 3a 3b 3c 3d 3e 3f  |0123456789:;<=>?|\n",
+            "00000020  40 41 42 43 44 45 46 47                           |@ABCDEFG|\n",
+            "                      ^^\n",
+        );
+        assert_eq!(error.hexdump(&printable()).to_string(), expected);
+    }
+
+    #[test]
+    fn hexdump_marks_the_end_of_input() {
+        let error = ParseError::InsufficientData { field: "amount", offset: 40, expected: 8, actual: 0 };
+        let dump = error.hexdump(&printable()).to_string();
+        assert!(dump.starts_with("amount needs 8 bytes but only 0 remain at offset 40\n"));
+        // The marker sits one past the last byte, after the column gap
+        assert!(dump.ends_with("|@ABCDEFG|\n                                   ^^ end of input\n"), "{dump}");
+    }
+
+    #[test]
+    fn hexdump_shows_only_rows_around_the_offset() {
+        let mut bytes = [0u8; 128];
+        bytes[80] = 0xFF;
+        let error = ParseError::InvalidValue { field: "executable", offset: 80 };
+        let expected = concat!(
+            "invalid executable at offset 80\n",
+            "00000030  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n",
+            "00000040  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n",
+            "00000050  ff 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n",
+            "          ^^\n",
+            "00000060  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n",
+        );
+        assert_eq!(error.hexdump(&bytes).to_string(), expected);
+    }
+
+    #[test]
+    fn offset_by_rebases_the_offset() {
+        let error = ParseError::UnknownDiscriminator { offset: 0, discriminator: 9 }.offset_by(10);
+        assert_eq!(error, ParseError::UnknownDiscriminator { offset: 10, discriminator: 9 });
+        let error = ParseError::InvalidAlignment { field: "AccountHeader", offset: 3, align: 8 }.offset_by(64);
+        assert_eq!(error.offset(), 67);
+    }
+
+    #[test]
+    fn v0_accounts_read_with_defaults() {
+        let bytes = account_bytes(sample(b"legacy").version(LayoutVersion::V0), 0);
+        assert_eq!(bytes.len(), AccountHeader::SIZE + 6);
+
+        let account = Account::from_bytes(&bytes).unwrap();
+        assert_eq!(account.version(), LayoutVersion::V0);
+        assert_eq!(account.header_len(), AccountHeader::SIZE);
+        assert_eq!(account.slot(), 0);
+        assert_eq!((account.amount(), account.data()), (42, &b"legacy"[..]));
+    }
+
+    #[test]
+    fn migrate_matches_migrate_into() {
+        let mut in_place = account_bytes(sample(b"legacy").version(LayoutVersion::V0), AccountHeaderV1::SIZE);
+        let legacy_len = AccountHeader::SIZE + 6;
+        let mut copied = vec![0xFF; LayoutVersion::CURRENT.header_len() + 6];
+        let written = Account::from_bytes(&in_place[..legacy_len]).unwrap().migrate_into(&mut copied).unwrap();
+        assert_eq!(written, copied.len());
+
+        AccountMut::from_bytes(&mut in_place).unwrap().migrate().unwrap();
+        assert_eq!(in_place, copied);
+
+        let account = Account::from_bytes(&in_place).unwrap();
+        assert_eq!(account.version(), LayoutVersion::CURRENT);
+        assert_eq!(account.slot(), 0);
+        assert_eq!((account.discriminator(), account.amount(), account.data()), (1, 42, &b"legacy"[..]));
+    }
+
+    #[test]
+    fn migrate_without_room_changes_nothing() {
+        let mut bytes = account_bytes(sample(b"legacy").version(LayoutVersion::V0), AccountHeaderV1::SIZE - 1);
+        let before = bytes.clone();
+        assert_eq!(
+            AccountMut::from_bytes(&mut bytes).unwrap().migrate(),
+            Err(ParseError::CapacityExceeded {
+                field: "account",
+                offset: 0,
+                required: LayoutVersion::CURRENT.header_len() + 6,
+                capacity: before.len(),
+            })
+        );
+        assert_eq!(bytes, before);
+
+        let mut short = vec![0u8; LayoutVersion::CURRENT.header_len() + 5];
+        let account = Account::from_bytes(&before).unwrap();
+        assert!(matches!(account.migrate_into(&mut short), Err(ParseError::CapacityExceeded { .. })));
+    }
+
+    #[test]
+    fn migrating_a_current_account_is_a_no_op() {
+        let mut bytes = account_bytes(sample(b"current"), 0);
+        let before = bytes.clone();
+        AccountMut::from_bytes(&mut bytes).unwrap().migrate().unwrap();
+        assert_eq!(bytes, before);
+    }
+
+    #[test]
+    fn versioned_headers_are_validated() {
+        let mut bytes = account_bytes(sample(b"x"), 0);
+        bytes[AccountHeader::VERSION_OFFSET] = 7;
+        assert_eq!(
+            Account::from_bytes(&bytes).err().unwrap(),
+            ParseError::UnknownVersion { offset: AccountHeader::VERSION_OFFSET, version: 7 }
+        );
+
+        // A V1 account cut inside the fields V1 appends
+        let bytes = account_bytes(sample(b""), 0);
+        assert_eq!(
+            Account::from_bytes(&bytes[..AccountHeader::SIZE + 3]).err().unwrap(),
+            ParseError::InsufficientData {
+                field: "AccountHeaderV1",
+                offset: AccountHeader::SIZE,
+                expected: AccountHeaderV1::SIZE,
+                actual: 3,
+            }
+        );
+    }
+}
@@ -0,0 +1,132 @@
+// Read-only memory map of a file of `[header][data][padding]` records, as
+// written by `AccountsDb::dump_accounts`.
+//
+// The file is mapped with `mmap(2)` instead of being read into a `Vec`, so
+// scanning a large snapshot only touches the pages that are actually read and
+// never copies account data onto the heap. Every record is validated once in
+// `AccountFile::open`; the views handed out afterwards borrow the mapping.
+//
+// Those views are plain `&[u8]`, which promise the bytes never change. The
+// kernel cannot stop another process from writing to or truncating the file
+// underneath the mapping, so `open` is `unsafe` and leaves that guarantee to
+// the caller, as `memmap2::Mmap::map` does.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+use super::{Account, AccountIter, ParseError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AccountFileError {
+    #[error("failed to map account file: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid account file: {0}")]
+    Parse(#[from] ParseError),
+}
+
+/// A validated, read-only mapping of an account file.
+pub struct AccountFile {
+    ptr: NonNull<u8>,
+    len: usize,
+    num_accounts: usize,
+}
+
+// The mapping is read-only and owned by this value, so sharing or moving it
+// between threads is no different from doing so with a `Box<[u8]>`
+unsafe impl Send for AccountFile {}
+unsafe impl Sync for AccountFile {}
+
+impl AccountFile {
+    /// Maps `path` and validates every record in it.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other
+    /// process, while the returned `AccountFile` is alive. The mapping is
+    /// private, so writes may or may not show through the borrowed views,
+    /// which would break their immutability, and reading past a truncated
+    /// end faults with `SIGBUS`.
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self, AccountFileError> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::FileTooLarge, "file does not fit in memory"))?;
+
+        // `mmap` rejects zero-length mappings
+        let ptr = if len == 0 {
+            NonNull::dangling()
+        } else {
+            let addr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if addr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error().into());
+            }
+            // Scans read front to back; this is only a hint, so a failure is
+            // ignored
+            unsafe { libc::madvise(addr, len, libc::MADV_SEQUENTIAL) };
+            NonNull::new(addr as *mut u8).expect("mmap does not return null on success")
+        };
+
+        // The descriptor can be closed; the mapping keeps the file alive
+        let mut mapping = AccountFile { ptr, len, num_accounts: 0 };
+        mapping.num_accounts = AccountIter::new(mapping.as_bytes()).try_fold(0, |count, record| {
+            record.map(|_| count + 1)
+        })?;
+        Ok(mapping)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        // The mapping is `len` readable bytes until `drop`, and nothing
+        // writes to it through this process
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn num_accounts(&self) -> usize {
+        self.num_accounts
+    }
+
+    /// Iterates the records with their offsets. The file was validated in
+    /// [`AccountFile::open`], so this never yields an error.
+    pub fn accounts(&self) -> AccountIter<'_> {
+        AccountIter::new(self.as_bytes())
+    }
+
+    /// The record at `offset`, as reported by [`AccountFile::accounts`].
+    pub fn account_at(&self, offset: usize) -> Result<Account<'_>, ParseError> {
+        let bytes = self.as_bytes();
+        let record = bytes.get(offset..).ok_or(ParseError::InsufficientData {
+            field: "account",
+            offset,
+            expected: 1,
+            actual: 0,
+        })?;
+        Account::from_bytes(record).map_err(|e| e.offset_by(offset))
+    }
+}
+
+impl Drop for AccountFile {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len) };
+        }
+    }
+}
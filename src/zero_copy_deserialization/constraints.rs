@@ -0,0 +1,240 @@
+// Owner, type and size checks applied while parsing, so an account that
+// passes `from_bytes_checked`/`from_bytes_with` is known to be the right
+// kind of account and no separate check can be forgotten.
+
+use crate::pubkey::Pubkey;
+
+use super::{Account, AccountHeader, AccountMut, ParseError};
+
+/// Requirements an account must meet on top of a valid layout.
+///
+/// Built with `const` setters, so a program can declare its constraints
+/// once:
+///
+/// ```
+/// # use sonic_test::pubkey::Pubkey;
+/// # use sonic_test::zero_copy_deserialization::constraints::AccountConstraints;
+/// const PROGRAM_ID: Pubkey = Pubkey::new([7; 32]);
+/// const VAULT: AccountConstraints = AccountConstraints::new()
+///     .owner(PROGRAM_ID)
+///     .discriminator(3)
+///     .min_data_len(8)
+///     .non_zero_amount();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountConstraints {
+    owner: Option<Pubkey>,
+    discriminator: Option<u8>,
+    min_data_len: usize,
+    max_data_len: usize,
+    non_zero_amount: bool,
+}
+
+impl Default for AccountConstraints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccountConstraints {
+    /// No requirements; every valid account passes.
+    pub const fn new() -> Self {
+        AccountConstraints {
+            owner: None,
+            discriminator: None,
+            min_data_len: 0,
+            max_data_len: usize::MAX,
+            non_zero_amount: false,
+        }
+    }
+
+    pub const fn owner(mut self, owner: Pubkey) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    pub const fn discriminator(mut self, discriminator: u8) -> Self {
+        self.discriminator = Some(discriminator);
+        self
+    }
+
+    pub const fn min_data_len(mut self, len: usize) -> Self {
+        self.min_data_len = len;
+        self
+    }
+
+    pub const fn max_data_len(mut self, len: usize) -> Self {
+        self.max_data_len = len;
+        self
+    }
+
+    pub const fn non_zero_amount(mut self) -> Self {
+        self.non_zero_amount = true;
+        self
+    }
+
+    /// Checks a parsed account, owner first, then discriminator, data length
+    /// and amount. Errors point at the header field that failed.
+    pub fn check(&self, account: &Account<'_>) -> Result<(), ParseError> {
+        if let Some(expected) = self.owner
+            && *account.owner() != expected
+        {
+            return Err(ParseError::OwnerMismatch {
+                offset: AccountHeader::OWNER_OFFSET,
+                expected,
+                actual: *account.owner(),
+            });
+        }
+
+        if let Some(expected) = self.discriminator
+            && account.discriminator() != expected
+        {
+            return Err(ParseError::DiscriminatorMismatch {
+                offset: AccountHeader::DISCRIMINATOR_OFFSET,
+                expected,
+                actual: account.discriminator(),
+            });
+        }
+
+        let length = account.data.len();
+        if !(self.min_data_len..=self.max_data_len).contains(&length) {
+            return Err(ParseError::DataLengthOutOfRange {
+                offset: AccountHeader::DATA_LEN_OFFSET,
+                length,
+                min: self.min_data_len,
+                max: self.max_data_len,
+            });
+        }
+
+        if self.non_zero_amount && account.amount() == 0 {
+            return Err(ParseError::ZeroAmount { offset: AccountHeader::AMOUNT_OFFSET });
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Account<'a> {
+    /// Parses an account that must be owned by `expected_owner` and carry
+    /// `expected_discriminator`.
+    pub fn from_bytes_checked(
+        bytes: &'a [u8],
+        expected_owner: &Pubkey,
+        expected_discriminator: u8,
+    ) -> Result<Self, ParseError> {
+        let constraints = AccountConstraints::new().owner(*expected_owner).discriminator(expected_discriminator);
+        Self::from_bytes_with(bytes, &constraints)
+    }
+
+    /// Parses an account and checks it against `constraints`.
+    pub fn from_bytes_with(bytes: &'a [u8], constraints: &AccountConstraints) -> Result<Self, ParseError> {
+        let account = Self::from_bytes(bytes)?;
+        constraints.check(&account)?;
+        Ok(account)
+    }
+}
+
+impl<'a> AccountMut<'a> {
+    /// Opens an account for writing after checking it against
+    /// `constraints`.
+    pub fn from_bytes_with(bytes: &'a mut [u8], constraints: &AccountConstraints) -> Result<Self, ParseError> {
+        constraints.check(&Account::from_bytes(bytes)?)?;
+        Self::from_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zero_copy_deserialization::tests::{account_bytes, sample};
+
+    const OWNER: Pubkey = Pubkey::new([7; 32]);
+    const OTHER: Pubkey = Pubkey::new([8; 32]);
+
+    fn account(owner: Pubkey, discriminator: u8, amount: u64, data: &[u8]) -> Vec<u8> {
+        account_bytes(sample(data).owner(owner).discriminator(discriminator).amount(amount), 0)
+    }
+
+    fn vault() -> AccountConstraints {
+        AccountConstraints::new().owner(OWNER).discriminator(3).min_data_len(2).max_data_len(4).non_zero_amount()
+    }
+
+    fn check(bytes: &[u8]) -> Result<(), ParseError> {
+        vault().check(&Account::from_bytes(bytes)?)
+    }
+
+    #[test]
+    fn valid_accounts_pass() {
+        assert_eq!(check(&account(OWNER, 3, 1, b"ab")), Ok(()));
+        assert_eq!(check(&account(OWNER, 3, 1, b"abcd")), Ok(()));
+
+        let anything = account(OTHER, 0, 0, b"");
+        assert_eq!(AccountConstraints::new().check(&Account::from_bytes(&anything).unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn failures_are_reported_in_order() {
+        // Every field starts out wrong, so each check only surfaces once the
+        // ones before it pass
+        assert_eq!(
+            check(&account(OTHER, 9, 0, b"")),
+            Err(ParseError::OwnerMismatch { offset: AccountHeader::OWNER_OFFSET, expected: OWNER, actual: OTHER })
+        );
+        assert_eq!(
+            check(&account(OWNER, 9, 0, b"")),
+            Err(ParseError::DiscriminatorMismatch { offset: AccountHeader::DISCRIMINATOR_OFFSET, expected: 3, actual: 9 })
+        );
+        assert_eq!(
+            check(&account(OWNER, 3, 0, b"")),
+            Err(ParseError::DataLengthOutOfRange { offset: AccountHeader::DATA_LEN_OFFSET, length: 0, min: 2, max: 4 })
+        );
+        assert_eq!(
+            check(&account(OWNER, 3, 0, b"ab")),
+            Err(ParseError::ZeroAmount { offset: AccountHeader::AMOUNT_OFFSET })
+        );
+    }
+
+    #[test]
+    fn data_length_bounds_are_inclusive() {
+        assert_eq!(check(&account(OWNER, 3, 1, b"abc")), Ok(()));
+        assert_eq!(
+            check(&account(OWNER, 3, 1, b"abcde")),
+            Err(ParseError::DataLengthOutOfRange { offset: AccountHeader::DATA_LEN_OFFSET, length: 5, min: 2, max: 4 })
+        );
+    }
+
+    #[test]
+    fn layout_errors_come_before_constraints() {
+        let bytes = account(OTHER, 9, 0, b"");
+        let err = Account::from_bytes_with(&bytes[..AccountHeader::SIZE - 1], &vault()).err().unwrap();
+        assert!(matches!(err, ParseError::InsufficientData { offset: 0, .. }));
+    }
+
+    #[test]
+    fn from_bytes_checked_checks_owner_and_discriminator() {
+        let bytes = account(OWNER, 3, 0, b"");
+        assert_eq!(Account::from_bytes_checked(&bytes, &OWNER, 3).unwrap().discriminator(), 3);
+
+        assert!(matches!(
+            Account::from_bytes_checked(&bytes, &OTHER, 3).err().unwrap(),
+            ParseError::OwnerMismatch { offset: AccountHeader::OWNER_OFFSET, .. }
+        ));
+        assert!(matches!(
+            Account::from_bytes_checked(&bytes, &OWNER, 4).err().unwrap(),
+            ParseError::DiscriminatorMismatch { offset: AccountHeader::DISCRIMINATOR_OFFSET, expected: 4, actual: 3 }
+        ));
+    }
+
+    #[test]
+    fn account_mut_is_checked_before_opening() {
+        let mut bytes = account(OWNER, 3, 5, b"ab");
+        AccountMut::from_bytes_with(&mut bytes, &vault()).unwrap().set_amount(6);
+        assert_eq!(Account::from_bytes(&bytes).unwrap().amount(), 6);
+
+        let mut empty = account(OWNER, 3, 0, b"ab");
+        assert_eq!(
+            AccountMut::from_bytes_with(&mut empty, &vault()).err().unwrap(),
+            ParseError::ZeroAmount { offset: AccountHeader::AMOUNT_OFFSET }
+        );
+    }
+}
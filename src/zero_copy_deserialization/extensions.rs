@@ -0,0 +1,371 @@
+// Type-length-value extensions stored in an account's data region after a
+// fixed base layout, in the style of Token-2022:
+//
+// [base: base_len][type: u16][length: u16][value: length][type: u16]...
+//
+// Integers are little-endian. Entries are packed with no padding and run to
+// the end of the data region. Offsets in `TlvEntry` and in errors from the
+// `Account`/`AccountMut` methods are relative to the start of the account,
+// i.e. the slice given to `Account::from_bytes`.
+
+use crate::endian::LeU16;
+
+use super::{Account, AccountMut, ParseError, ZeroCopy};
+
+/// Prefix of every extension entry.
+#[repr(C)]
+#[derive(Debug, ZeroCopy)]
+pub struct ExtensionHeader {
+    pub extension_type: LeU16,
+    pub length: LeU16,
+}
+
+/// One extension found by [`TlvIter`].
+#[derive(Debug, Clone, Copy)]
+pub struct TlvEntry<'a> {
+    /// Offset of the entry's `ExtensionHeader`.
+    pub offset: usize,
+    pub extension_type: u16,
+    pub value: &'a [u8],
+}
+
+/// Walks the extensions after the base layout of a data region.
+///
+/// Iteration stops after the first error, which reports the offset of the
+/// entry that failed to parse.
+pub struct TlvIter<'a> {
+    data: &'a [u8],
+    // Offset of `data` within the account, added to reported offsets
+    origin: usize,
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> TlvIter<'a> {
+    /// Iterates the extensions of `data`, skipping the first `base_len`
+    /// bytes. Offsets are relative to `data`.
+    pub fn new(data: &'a [u8], base_len: usize) -> Self {
+        TlvIter { data, origin: 0, offset: base_len, failed: false }
+    }
+}
+
+impl<'a> Iterator for TlvIter<'a> {
+    type Item = Result<TlvEntry<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset == self.data.len() {
+            return None;
+        }
+
+        match parse_entry(self.data, self.offset) {
+            Ok(entry) => {
+                self.offset += ExtensionHeader::SIZE + entry.value.len();
+                Some(Ok(TlvEntry { offset: entry.offset + self.origin, ..entry }))
+            }
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error.offset_by(self.origin)))
+            }
+        }
+    }
+}
+
+// Parses the entry at `offset` of `data`.
+fn parse_entry(data: &[u8], offset: usize) -> Result<TlvEntry<'_>, ParseError> {
+    let rest = data.get(offset..).ok_or(ParseError::InsufficientData {
+        field: "base layout",
+        offset: 0,
+        expected: offset,
+        actual: data.len(),
+    })?;
+    let header = ExtensionHeader::from_bytes(rest).map_err(|e| e.offset_by(offset))?;
+
+    let length = header.length.get() as usize;
+    let remaining = rest.len() - ExtensionHeader::SIZE;
+    if length > remaining {
+        return Err(ParseError::InvalidDataLength {
+            field: "extension length",
+            offset,
+            length,
+            remaining,
+        });
+    }
+
+    let value_start = ExtensionHeader::SIZE;
+    Ok(TlvEntry {
+        offset,
+        extension_type: header.extension_type.get(),
+        value: &rest[value_start..value_start + length],
+    })
+}
+
+// First entry of `extension_type`, validating every entry before it.
+fn find<'a>(mut entries: TlvIter<'a>, extension_type: u16) -> Result<Option<TlvEntry<'a>>, ParseError> {
+    entries
+        .find_map(|entry| match entry {
+            Ok(entry) if entry.extension_type != extension_type => None,
+            other => Some(other),
+        })
+        .transpose()
+}
+
+impl<'a> Account<'a> {
+    /// Extensions stored after the first `base_len` bytes of the data.
+    pub fn extensions(&self, base_len: usize) -> TlvIter<'a> {
+        TlvIter { origin: self.header_len(), ..TlvIter::new(self.data, base_len) }
+    }
+
+    /// Value of the first extension of `extension_type`, if any.
+    pub fn extension(&self, base_len: usize, extension_type: u16) -> Result<Option<&'a [u8]>, ParseError> {
+        Ok(find(self.extensions(base_len), extension_type)?.map(|entry| entry.value))
+    }
+}
+
+impl AccountMut<'_> {
+    /// Mutable value of the first extension of `extension_type`, if any.
+    pub fn extension_mut(&mut self, base_len: usize, extension_type: u16) -> Result<Option<&mut [u8]>, ParseError> {
+        let account = self.as_account();
+        let Some(entry) = find(account.extensions(base_len), extension_type)? else {
+            return Ok(None);
+        };
+        let start = entry.offset - account.header_len() + ExtensionHeader::SIZE;
+        let end = start + entry.value.len();
+        Ok(Some(&mut self.data_mut()[start..end]))
+    }
+
+    /// Resizes the extension of `extension_type` to `new_len` bytes, or
+    /// appends it if it is missing, and returns its value.
+    ///
+    /// Later entries are moved to stay packed and bytes exposed by growing
+    /// are zeroed. The whole extension region is validated first, so a
+    /// malformed entry is reported instead of being moved.
+    pub fn resize_extension(
+        &mut self,
+        base_len: usize,
+        extension_type: u16,
+        new_len: usize,
+    ) -> Result<&mut [u8], ParseError> {
+        let account = self.as_account();
+        let account_header_len = account.header_len();
+        let data_len = account.data.len();
+        let mut existing = None;
+        for entry in account.extensions(base_len) {
+            let entry = entry?;
+            if existing.is_none() && entry.extension_type == extension_type {
+                existing = Some(entry);
+            }
+        }
+
+        // Entry position, old value length, the start of the entries that
+        // follow it, all relative to the data, and the size of any new
+        // extension header
+        let (entry_offset, old_len, tail_start, added_header_len) = match existing {
+            Some(entry) => {
+                let offset = entry.offset - account_header_len;
+                let old_len = entry.value.len();
+                (offset, old_len, offset + ExtensionHeader::SIZE + old_len, 0)
+            }
+            None => (data_len, 0, data_len, ExtensionHeader::SIZE),
+        };
+
+        if new_len > u16::MAX as usize {
+            return Err(ParseError::CapacityExceeded {
+                field: "extension length",
+                offset: account_header_len + entry_offset,
+                required: new_len,
+                capacity: u16::MAX as usize,
+            });
+        }
+
+        let value_start = entry_offset + ExtensionHeader::SIZE;
+        let new_data_len = data_len + added_header_len + new_len - old_len;
+
+        if new_data_len > data_len {
+            // Grow first so the tail has room to move right
+            self.resize_data(new_data_len)?;
+            self.data_mut().copy_within(tail_start..data_len, value_start + new_len);
+            self.data_mut()[value_start + old_len..value_start + new_len].fill(0);
+        } else {
+            self.data_mut().copy_within(tail_start..data_len, value_start + new_len);
+            self.resize_data(new_data_len)?;
+        }
+
+        let data = self.data_mut();
+        let header = ExtensionHeader::from_bytes_mut(&mut data[entry_offset..])?;
+        header.extension_type.set(extension_type);
+        header.length.set(new_len as u16);
+        Ok(&mut data[value_start..value_start + new_len])
+    }
+
+    /// Stores `value` as the extension of `extension_type`, resizing or
+    /// appending it as needed.
+    pub fn set_extension(&mut self, base_len: usize, extension_type: u16, value: &[u8]) -> Result<(), ParseError> {
+        self.resize_extension(base_len, extension_type, value.len())?.copy_from_slice(value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zero_copy_deserialization::{AccountWriter, LayoutVersion};
+
+    const BASE_LEN: usize = 8;
+    const MEMO: u16 = 1;
+    const FEE: u16 = 2;
+    const HEADER_LEN: usize = LayoutVersion::V1.header_len();
+
+    // A V1 account with an 8-byte base layout and `spare` bytes of capacity
+    fn account(spare: usize) -> Vec<u8> {
+        let writer = AccountWriter::new().data(&[0xBB; BASE_LEN]);
+        let mut bytes = vec![0u8; writer.encoded_len() + spare];
+        writer.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn appends_and_looks_up_extensions() {
+        let mut bytes = account(64);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        account.set_extension(BASE_LEN, MEMO, b"hi").unwrap();
+        account.set_extension(BASE_LEN, FEE, &[1, 2]).unwrap();
+
+        let account = account.as_account();
+        let entries: Vec<_> = account.extensions(BASE_LEN).map(Result::unwrap).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].offset, HEADER_LEN + BASE_LEN);
+        assert_eq!((entries[0].extension_type, entries[0].value), (MEMO, &b"hi"[..]));
+        assert_eq!(entries[1].offset, HEADER_LEN + BASE_LEN + ExtensionHeader::SIZE + 2);
+        assert_eq!((entries[1].extension_type, entries[1].value), (FEE, &[1, 2][..]));
+
+        assert_eq!(account.extension(BASE_LEN, FEE), Ok(Some(&[1, 2][..])));
+        assert_eq!(account.extension(BASE_LEN, 9), Ok(None));
+        assert_eq!(account.data()[..BASE_LEN], [0xBB; BASE_LEN]);
+        assert_eq!(account.data().len(), BASE_LEN + 2 * ExtensionHeader::SIZE + 4);
+    }
+
+    #[test]
+    fn growing_moves_later_entries_and_zeroes_new_bytes() {
+        let mut bytes = account(64);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        account.set_extension(BASE_LEN, MEMO, b"hi").unwrap();
+        account.set_extension(BASE_LEN, FEE, &[1, 2]).unwrap();
+
+        let value = account.resize_extension(BASE_LEN, MEMO, 5).unwrap();
+        assert_eq!(value, b"hi\0\0\0");
+        value.copy_from_slice(b"hello");
+
+        let account = account.as_account();
+        assert_eq!(account.extension(BASE_LEN, MEMO), Ok(Some(&b"hello"[..])));
+        assert_eq!(account.extension(BASE_LEN, FEE), Ok(Some(&[1, 2][..])));
+        assert_eq!(account.data().len(), BASE_LEN + 2 * ExtensionHeader::SIZE + 7);
+    }
+
+    #[test]
+    fn shrinking_to_zero_keeps_the_entry_and_moves_later_entries() {
+        let mut bytes = account(64);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        account.set_extension(BASE_LEN, MEMO, b"hello").unwrap();
+        account.set_extension(BASE_LEN, FEE, &[1, 2]).unwrap();
+
+        assert!(account.resize_extension(BASE_LEN, MEMO, 0).unwrap().is_empty());
+        account.extension_mut(BASE_LEN, FEE).unwrap().unwrap().copy_from_slice(&[3, 4]);
+
+        let account = account.as_account();
+        assert_eq!(account.extension(BASE_LEN, MEMO), Ok(Some(&[][..])));
+        assert_eq!(account.extension(BASE_LEN, FEE), Ok(Some(&[3, 4][..])));
+        assert_eq!(account.data().len(), BASE_LEN + 2 * ExtensionHeader::SIZE + 2);
+    }
+
+    #[test]
+    fn oversized_extension_reports_account_offset() {
+        let mut bytes = account(64);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        account.set_extension(BASE_LEN, MEMO, b"hi").unwrap();
+        let too_long = u16::MAX as usize + 1;
+
+        // Growing the existing extension points at its header
+        let error = account.resize_extension(BASE_LEN, MEMO, too_long).unwrap_err();
+        assert_eq!(
+            error,
+            ParseError::CapacityExceeded {
+                field: "extension length",
+                offset: HEADER_LEN + BASE_LEN,
+                required: too_long,
+                capacity: u16::MAX as usize,
+            }
+        );
+
+        // A new extension would be appended after the existing one
+        let error = account.resize_extension(BASE_LEN, FEE, too_long).unwrap_err();
+        assert_eq!(error.offset(), HEADER_LEN + BASE_LEN + ExtensionHeader::SIZE + 2);
+        assert_eq!(account.as_account().data().len(), BASE_LEN + ExtensionHeader::SIZE + 2);
+    }
+
+    #[test]
+    fn growing_past_capacity_changes_nothing() {
+        let mut bytes = account(ExtensionHeader::SIZE + 2);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        account.set_extension(BASE_LEN, MEMO, b"hi").unwrap();
+        let before = account.as_account().data().to_vec();
+
+        let error = account.resize_extension(BASE_LEN, MEMO, 3).unwrap_err();
+        assert!(matches!(error, ParseError::CapacityExceeded { field: "data", .. }));
+        assert_eq!(account.as_account().data(), &before[..]);
+    }
+
+    #[test]
+    fn corrupt_length_reports_its_entry_offset() {
+        let mut bytes = account(64);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        account.set_extension(BASE_LEN, MEMO, b"hi").unwrap();
+        account.set_extension(BASE_LEN, FEE, &[1, 2]).unwrap();
+        let second = BASE_LEN + ExtensionHeader::SIZE + 2;
+        account.data_mut()[second + 2..second + 4].copy_from_slice(&40u16.to_le_bytes());
+
+        let expected = ParseError::InvalidDataLength {
+            field: "extension length",
+            offset: HEADER_LEN + second,
+            length: 40,
+            remaining: 2,
+        };
+        let view = account.as_account();
+        let results: Vec<_> = view.extensions(BASE_LEN).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert_eq!(results[1].as_ref().unwrap_err(), &expected);
+        assert_eq!(view.extension(BASE_LEN, FEE), Err(expected.clone()));
+        // The entry before the corrupt one is still found
+        assert_eq!(view.extension(BASE_LEN, MEMO), Ok(Some(&b"hi"[..])));
+
+        // Resizing validates every entry before moving any
+        assert_eq!(account.resize_extension(BASE_LEN, MEMO, 4).unwrap_err(), expected);
+    }
+
+    #[test]
+    fn truncated_entry_header_reports_its_offset() {
+        let mut bytes = account(64);
+        let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+        account.resize_data(BASE_LEN + 3).unwrap();
+
+        let error = account.as_account().extensions(BASE_LEN).next().unwrap().unwrap_err();
+        assert_eq!(
+            error,
+            ParseError::InsufficientData {
+                field: "ExtensionHeader",
+                offset: HEADER_LEN + BASE_LEN,
+                expected: ExtensionHeader::SIZE,
+                actual: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn standalone_iterator_reports_data_offsets() {
+        let data = [0u8, 0, 7, 0, 1, 0, 0xAA];
+        let entries: Vec<_> = TlvIter::new(&data, 2).collect();
+        assert_eq!(entries.len(), 1);
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!((entry.offset, entry.extension_type, entry.value), (2, 7, &[0xAA][..]));
+    }
+}
@@ -0,0 +1,283 @@
+// Program input buffer, as serialized by the SBF loader for an invocation:
+//
+// [num_accounts: u64]
+// per account, either a duplicate
+//   [dup_info: u8 = index of the first occurrence][padding: 7]
+// or
+//   [dup_info: u8 = 0xFF][is_signer: u8][is_writable: u8][executable: u8]
+//   [original_data_len: u32][key: 32][owner: 32][lamports: u64][data_len: u64]
+//   [data: data_len][realloc padding: 10 KiB][padding to 8][rent_epoch: u64]
+// [instruction_data_len: u64][instruction_data][program_id: 32]
+//
+// Integers are little-endian. Offsets of the alignment padding are relative
+// to the start of the buffer, which the loader places on an 8-byte boundary.
+
+use crate::endian::{LeU32, LeU64};
+use crate::pubkey::Pubkey;
+
+use super::{ParseError, ZeroCopy};
+
+/// `dup_info` value of an account that is not a duplicate.
+pub const NON_DUP_MARKER: u8 = u8::MAX;
+
+/// Space reserved after each account's data so the program can grow it.
+pub const MAX_PERMITTED_DATA_INCREASE: usize = 10 * 1024;
+
+/// Alignment the loader pads each account's data region to.
+pub const BPF_ALIGN_OF_U128: usize = 8;
+
+/// Fixed part of a non-duplicate account entry.
+#[repr(C)]
+#[derive(Debug, ZeroCopy)]
+pub struct SerializedAccountHeader {
+    pub dup_info: u8,
+    pub is_signer: u8,
+    pub is_writable: u8,
+    pub executable: u8,
+    /// Data length before the invocation, so the program can tell how far
+    /// it has grown into the realloc space.
+    pub original_data_len: LeU32,
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: LeU64,
+    pub data_len: LeU64,
+}
+
+/// Size of a duplicate entry: the marker byte plus padding.
+pub const DUPLICATE_ENTRY_SIZE: usize = 8;
+
+/// Bytes a non-duplicate entry at `offset` with `data_len` bytes of data
+/// occupies, up to and including its `rent_epoch`.
+pub fn account_entry_len(offset: usize, data_len: usize) -> usize {
+    let rent_epoch_offset = (offset + SerializedAccountHeader::SIZE + data_len + MAX_PERMITTED_DATA_INCREASE)
+        .next_multiple_of(BPF_ALIGN_OF_U128);
+    rent_epoch_offset + 8 - offset
+}
+
+/// One account entry of a [`ProgramInput`].
+#[derive(Debug, Clone, Copy)]
+pub enum InputAccount<'a> {
+    Account(InputAccountView<'a>),
+    /// Repeats the account at this position in the account list.
+    Duplicate(u8),
+}
+
+/// Zero-copy view of a non-duplicate account entry.
+#[derive(Debug, Clone, Copy)]
+pub struct InputAccountView<'a> {
+    header: &'a SerializedAccountHeader,
+    data: &'a [u8],
+    rent_epoch: u64,
+}
+
+impl<'a> InputAccountView<'a> {
+    pub fn header(&self) -> &'a SerializedAccountHeader {
+        self.header
+    }
+
+    pub fn is_signer(&self) -> bool {
+        self.header.is_signer != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.header.is_writable != 0
+    }
+
+    pub fn executable(&self) -> bool {
+        self.header.executable != 0
+    }
+
+    pub fn key(&self) -> &'a Pubkey {
+        &self.header.key
+    }
+
+    pub fn owner(&self) -> &'a Pubkey {
+        &self.header.owner
+    }
+
+    pub fn lamports(&self) -> u64 {
+        self.header.lamports.get()
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn original_data_len(&self) -> u32 {
+        self.header.original_data_len.get()
+    }
+
+    pub fn rent_epoch(&self) -> u64 {
+        self.rent_epoch
+    }
+}
+
+/// Zero-copy view of a program input buffer.
+///
+/// The whole buffer is validated by [`ProgramInput::from_bytes`], so walking
+/// the accounts afterwards cannot fail.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramInput<'a> {
+    bytes: &'a [u8],
+    num_accounts: usize,
+    instruction_data: &'a [u8],
+    program_id: &'a Pubkey,
+}
+
+impl<'a> ProgramInput<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        let num_accounts = read_u64(bytes, 0, "num_accounts")? as usize;
+
+        let mut offset = 8;
+        for index in 0..num_accounts {
+            let (entry, len) = parse_entry(bytes, offset)?;
+            if let InputAccount::Duplicate(original) = entry
+                && original as usize >= index
+            {
+                return Err(ParseError::InvalidValue { field: "dup_info", offset });
+            }
+            offset += len;
+        }
+
+        let instruction_data_len = read_u64(bytes, offset, "instruction_data_len")? as usize;
+        offset += 8;
+        let remaining = bytes.len() - offset;
+        let instruction_data = bytes[offset..]
+            .get(..instruction_data_len)
+            .ok_or(ParseError::InvalidDataLength {
+                field: "instruction_data_len",
+                offset,
+                length: instruction_data_len,
+                remaining,
+            })?;
+        offset += instruction_data_len;
+
+        let program_id = bytes[offset..]
+            .first_chunk::<32>()
+            .map(Pubkey::from_bytes_ref)
+            .ok_or(ParseError::InsufficientData {
+                field: "program_id",
+                offset,
+                expected: 32,
+                actual: bytes.len() - offset,
+            })?;
+
+        Ok(ProgramInput { bytes, num_accounts, instruction_data, program_id })
+    }
+
+    pub fn num_accounts(&self) -> usize {
+        self.num_accounts
+    }
+
+    pub fn accounts(&self) -> InputAccounts<'a> {
+        InputAccounts { bytes: self.bytes, offset: 8, remaining: self.num_accounts }
+    }
+
+    /// Resolves duplicates to the entry they refer to.
+    pub fn account(&self, index: usize) -> Option<InputAccountView<'a>> {
+        match self.accounts().nth(index)? {
+            InputAccount::Account(view) => Some(view),
+            InputAccount::Duplicate(original) => self.account(original as usize),
+        }
+    }
+
+    pub fn instruction_data(&self) -> &'a [u8] {
+        self.instruction_data
+    }
+
+    pub fn program_id(&self) -> &'a Pubkey {
+        self.program_id
+    }
+}
+
+/// Iterator over the account entries of a validated [`ProgramInput`].
+pub struct InputAccounts<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for InputAccounts<'a> {
+    type Item = InputAccount<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let (entry, len) =
+            parse_entry(self.bytes, self.offset).expect("validated in ProgramInput::from_bytes");
+        self.offset += len;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for InputAccounts<'_> {}
+
+// Parses the entry at `offset` and returns it with its length in bytes.
+fn parse_entry(bytes: &[u8], offset: usize) -> Result<(InputAccount<'_>, usize), ParseError> {
+    let dup_info = *bytes.get(offset).ok_or(ParseError::InsufficientData {
+        field: "dup_info",
+        offset,
+        expected: 1,
+        actual: 0,
+    })?;
+
+    if dup_info != NON_DUP_MARKER {
+        if bytes.len() - offset < DUPLICATE_ENTRY_SIZE {
+            return Err(ParseError::InsufficientData {
+                field: "duplicate account",
+                offset,
+                expected: DUPLICATE_ENTRY_SIZE,
+                actual: bytes.len() - offset,
+            });
+        }
+        return Ok((InputAccount::Duplicate(dup_info), DUPLICATE_ENTRY_SIZE));
+    }
+
+    let header = SerializedAccountHeader::from_bytes(&bytes[offset..]).map_err(|e| e.offset_by(offset))?;
+    for (field, value, field_offset) in [
+        ("is_signer", header.is_signer, SerializedAccountHeader::IS_SIGNER_OFFSET),
+        ("is_writable", header.is_writable, SerializedAccountHeader::IS_WRITABLE_OFFSET),
+        ("executable", header.executable, SerializedAccountHeader::EXECUTABLE_OFFSET),
+    ] {
+        if value > 1 {
+            return Err(ParseError::InvalidValue { field, offset: offset + field_offset });
+        }
+    }
+
+    let data_start = offset + SerializedAccountHeader::SIZE;
+    let data_len = header.data_len.get();
+    let remaining = bytes.len() - data_start;
+    let data_len = usize::try_from(data_len)
+        .ok()
+        .filter(|&len| len <= remaining)
+        .ok_or(ParseError::InvalidDataLength {
+            field: "data_len",
+            offset: data_start,
+            length: data_len.try_into().unwrap_or(usize::MAX),
+            remaining,
+        })?;
+    let data = &bytes[data_start..data_start + data_len];
+
+    let len = account_entry_len(offset, data_len);
+    let rent_epoch = read_u64(bytes, offset + len - 8, "rent_epoch")?;
+
+    Ok((InputAccount::Account(InputAccountView { header, data, rent_epoch }), len))
+}
+
+fn read_u64(bytes: &[u8], offset: usize, field: &'static str) -> Result<u64, ParseError> {
+    let value = bytes.get(offset..).and_then(|rest| rest.first_chunk::<8>());
+    let value = value.ok_or(ParseError::InsufficientData {
+        field,
+        offset,
+        expected: 8,
+        actual: bytes.len().saturating_sub(offset),
+    })?;
+    Ok(LeU64::from_bytes(*value).get())
+}
@@ -0,0 +1,159 @@
+// Tests for `AccountFile` over files written by `AccountsDb::dump_accounts`.
+
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sonic_test::account_state_management::{
+    AccountState, AccountStateRecord, AccountStateRecordHeader, AccountsDb,
+};
+use sonic_test::pubkey::Pubkey;
+use sonic_test::zero_copy_deserialization::account_file::{AccountFile, AccountFileError};
+use sonic_test::zero_copy_deserialization::{
+    AccountHeader, LayoutVersion, ParseError, RECORD_ALIGNMENT,
+};
+
+// A file in the temp directory, removed on drop
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &[u8]) -> Self {
+        let path = std::env::temp_dir().join(format!("sonic-test-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        TempFile(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn accounts() -> Vec<(Pubkey, AccountState)> {
+    vec![
+        (Pubkey::new([1; 32]), AccountState::new(100, b"first".to_vec(), Pubkey::new([9; 32]))),
+        (Pubkey::new([2; 32]), AccountState::new(200, Vec::new(), Pubkey::default())),
+        (
+            Pubkey::new([3; 32]),
+            AccountState { executable: true, rent_epoch: 7, ..AccountState::new(300, vec![0xCD; 100], Pubkey::default()) },
+        ),
+    ]
+}
+
+fn dump() -> Vec<u8> {
+    let db = AccountsDb::new();
+    for (pubkey, state) in accounts() {
+        db.create_account(pubkey, state);
+    }
+    db.dump_accounts().unwrap()
+}
+
+// Every file is private to its test and left alone while it is mapped
+fn open(path: &Path) -> Result<AccountFile, AccountFileError> {
+    unsafe { AccountFile::open(path) }
+}
+
+fn parse_error(result: Result<AccountFile, AccountFileError>) -> ParseError {
+    match result {
+        Err(AccountFileError::Parse(error)) => error,
+        Err(error) => panic!("unexpected error: {error}"),
+        Ok(_) => panic!("invalid file was accepted"),
+    }
+}
+
+#[test]
+fn empty_file_has_no_accounts() {
+    let file = TempFile::new("empty", b"");
+    let mapping = open(&file.0).unwrap();
+
+    assert!(mapping.is_empty());
+    assert_eq!(mapping.len(), 0);
+    assert_eq!(mapping.as_bytes(), b"");
+    assert_eq!(mapping.num_accounts(), 0);
+    assert_eq!(mapping.accounts().count(), 0);
+    assert_eq!(
+        mapping.account_at(0).err().unwrap(),
+        ParseError::InsufficientData { field: "AccountHeader", offset: 0, expected: AccountHeader::SIZE, actual: 0 }
+    );
+}
+
+#[test]
+fn iterates_a_dump() {
+    let bytes = dump();
+    let file = TempFile::new("dump", &bytes);
+    let mapping = open(&file.0).unwrap();
+
+    assert_eq!(mapping.as_bytes(), &bytes[..]);
+    assert_eq!(mapping.num_accounts(), 3);
+
+    let mut expected_offset = 0;
+    for (record, (pubkey, state)) in mapping.accounts().zip(accounts()) {
+        let (offset, account) = record.unwrap();
+        assert_eq!(offset, expected_offset);
+        assert_eq!(account.parse_as::<AccountStateRecord>().unwrap().header.pubkey, pubkey);
+        assert_eq!(AccountState::try_from(account).unwrap(), state);
+        expected_offset = (offset + state.encoded_len()).next_multiple_of(RECORD_ALIGNMENT);
+    }
+}
+
+#[test]
+fn account_at_reads_any_record_offset() {
+    let bytes = dump();
+    let file = TempFile::new("account-at", &bytes);
+    let mapping = open(&file.0).unwrap();
+
+    let offsets: Vec<usize> = mapping.accounts().map(|record| record.unwrap().0).collect();
+    for (&offset, (_, state)) in offsets.iter().zip(accounts()).rev() {
+        let account = mapping.account_at(offset).unwrap();
+        assert_eq!(AccountState::try_from(account).unwrap(), state);
+    }
+
+    assert_eq!(
+        mapping.account_at(bytes.len() + 1).err().unwrap(),
+        ParseError::InsufficientData { field: "account", offset: bytes.len() + 1, expected: 1, actual: 0 }
+    );
+    // Inside the third record's data, whose 0xCD bytes are no valid version
+    let inside = offsets[2] + LayoutVersion::CURRENT.header_len() + AccountStateRecordHeader::SIZE;
+    assert_eq!(
+        mapping.account_at(inside).err().unwrap(),
+        ParseError::UnknownVersion { offset: inside + AccountHeader::VERSION_OFFSET, version: 0xCD }
+    );
+}
+
+#[test]
+fn truncated_last_record_is_reported_at_its_offset() {
+    let bytes = dump();
+    let last = {
+        let file = TempFile::new("offsets", &bytes);
+        let mapping = open(&file.0).unwrap();
+        mapping.accounts().last().unwrap().unwrap().0
+    };
+    let data_start = last + LayoutVersion::CURRENT.header_len();
+
+    // Cut in the middle of the last record's data
+    let file = TempFile::new("truncated-data", &bytes[..data_start + 10]);
+    assert_eq!(
+        parse_error(open(&file.0)),
+        ParseError::InvalidDataLength {
+            field: "data_len",
+            offset: data_start,
+            length: accounts()[2].1.encoded_len() - LayoutVersion::CURRENT.header_len(),
+            remaining: 10,
+        }
+    );
+
+    // Cut in the middle of its header
+    let file = TempFile::new("truncated-header", &bytes[..last + 20]);
+    assert_eq!(
+        parse_error(open(&file.0)),
+        ParseError::InsufficientData { field: "AccountHeader", offset: last, expected: AccountHeader::SIZE, actual: 20 }
+    );
+}
+
+#[test]
+fn missing_file_is_an_io_error() {
+    let path = std::env::temp_dir().join(format!("sonic-test-{}-missing", std::process::id()));
+    assert!(matches!(open(&path), Err(AccountFileError::Io(_))));
+}
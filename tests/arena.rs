@@ -0,0 +1,94 @@
+// Tests for `Arena`. Run them under Miri to check that allocations never
+// alias and stay valid across chunk growth:
+//
+//     cargo +nightly miri test --test arena
+
+use sonic_test::unsafe_rust_memory_management::arena::Arena;
+
+#[test]
+fn allocations_do_not_overlap() {
+    let arena = Arena::with_capacity(64);
+    let a = arena.alloc_slice_fill(16, 0xAAu8);
+    let b = arena.alloc(0xBBBB_BBBBu32);
+    let c = arena.alloc_slice_copy(b"ccc");
+
+    // Write through every allocation while the others are live
+    a[15] = 1;
+    *b += 1;
+    c[0] = b'C';
+    assert_eq!(a[..15], [0xAA; 15]);
+    assert_eq!(a[15], 1);
+    assert_eq!(*b, 0xBBBB_BBBC);
+    assert_eq!(c, b"Ccc");
+}
+
+#[test]
+fn allocations_are_aligned() {
+    #[derive(Clone, Copy)]
+    #[repr(align(64))]
+    struct Wide(u8);
+
+    let arena = Arena::new();
+    arena.alloc(1u8);
+    let wide = arena.alloc(Wide(7));
+    assert!((wide as *const Wide as usize).is_multiple_of(64));
+    let words = arena.alloc_slice_fill(3, 0u64);
+    assert!((words.as_ptr() as usize).is_multiple_of(align_of::<u64>()));
+    assert_eq!(wide.0, 7);
+}
+
+#[test]
+fn fill_writes_every_element() {
+    let arena = Arena::new();
+    let values = arena.alloc_slice_fill(100, (1u16, 2u8));
+    assert!(values.iter().all(|&value| value == (1, 2)));
+}
+
+#[test]
+fn growth_keeps_earlier_allocations_valid() {
+    let arena = Arena::with_capacity(32);
+    let first = arena.alloc_slice_copy(&[1u8; 24]);
+    // Neither fits in the first chunk
+    let second = arena.alloc_slice_fill(100, 2u8);
+    let third = arena.alloc_slice_fill(1_000, 3u8);
+
+    first[0] = 9;
+    assert_eq!(first[..2], [9, 1]);
+    assert!(second.iter().all(|&b| b == 2));
+    assert!(third.iter().all(|&b| b == 3));
+    assert!(arena.capacity() >= 32 + 100 + 1_000);
+}
+
+#[test]
+fn reset_keeps_the_largest_chunk() {
+    let mut arena = Arena::with_capacity(16);
+    arena.alloc_slice_fill(1_000, 0u8);
+    let capacity = arena.capacity();
+    let largest = capacity - 16;
+    assert!(arena.allocated_bytes() >= 1_000);
+
+    arena.reset();
+    assert_eq!(arena.allocated_bytes(), 0);
+    assert_eq!(arena.capacity(), largest);
+
+    // Memory is reused after a reset, and stale contents are overwritten
+    let reused = arena.alloc_slice_fill(1_000, 5u8);
+    assert!(reused.iter().all(|&b| b == 5));
+    assert_eq!(arena.capacity(), largest);
+}
+
+#[test]
+fn empty_and_zero_size_allocations_need_no_memory() {
+    let arena = Arena::new();
+    let empty: &mut [u64] = arena.alloc_slice_copy(&[]);
+    assert!(empty.is_empty());
+    let none = arena.alloc_slice_fill(0, 1u32);
+    assert!(none.is_empty());
+    let unit = arena.alloc(());
+    *unit = ();
+    let zsts = arena.alloc_slice_fill(10, ());
+    assert_eq!(zsts.len(), 10);
+
+    assert_eq!(arena.allocated_bytes(), 0);
+    assert_eq!(arena.capacity(), 0);
+}
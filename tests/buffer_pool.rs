@@ -0,0 +1,113 @@
+// Tests for `BufferPool` and `Lease`: the capacity limit, blocking leases,
+// the statistics and clearing buffers before they are reused.
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use sonic_test::unsafe_rust_memory_management::{BufferPool, PoolStats};
+
+const LEN: usize = 64;
+const ALIGN: usize = 16;
+
+#[test]
+fn leases_are_zeroed_and_aligned() {
+    let pool = BufferPool::new(LEN, ALIGN, 2);
+    let lease = pool.lease();
+    assert_eq!(lease.len(), LEN);
+    assert!(lease.iter().all(|&b| b == 0));
+    assert!((lease.as_ptr() as usize).is_multiple_of(ALIGN));
+}
+
+#[test]
+fn never_allocates_more_than_capacity() {
+    let pool = BufferPool::new(LEN, ALIGN, 3);
+    let leases: Vec<_> = (0..3).map(|_| pool.try_lease().unwrap()).collect();
+    assert!(pool.try_lease().is_none());
+    assert_eq!(pool.stats(), PoolStats { in_use: 3, idle: 0, allocated: 3, recycled: 0 });
+
+    drop(leases);
+    assert_eq!(pool.stats(), PoolStats { in_use: 0, idle: 3, allocated: 3, recycled: 0 });
+
+    // Reuses the returned buffers instead of allocating
+    let leases: Vec<_> = (0..3).map(|_| pool.try_lease().unwrap()).collect();
+    assert!(pool.try_lease().is_none());
+    assert_eq!(pool.stats(), PoolStats { in_use: 3, idle: 0, allocated: 3, recycled: 3 });
+    drop(leases);
+}
+
+#[test]
+fn allocates_lazily_and_counts_recycled_leases() {
+    let pool = BufferPool::new(LEN, ALIGN, 4);
+    assert_eq!(pool.stats(), PoolStats::default());
+
+    let first = pool.lease();
+    assert_eq!(pool.stats(), PoolStats { in_use: 1, idle: 0, allocated: 1, recycled: 0 });
+    drop(first);
+
+    // The same buffer serves every sequential lease
+    for recycled in 1..=5 {
+        let lease = pool.lease();
+        assert_eq!(pool.stats(), PoolStats { in_use: 1, idle: 0, allocated: 1, recycled });
+        drop(lease);
+    }
+    assert_eq!(pool.stats(), PoolStats { in_use: 0, idle: 1, allocated: 1, recycled: 5 });
+}
+
+#[test]
+fn returned_buffers_are_cleared() {
+    let pool = BufferPool::new(LEN, ALIGN, 1);
+    let mut lease = pool.lease();
+    lease.fill(0xAB);
+    let address = lease.as_ptr();
+    drop(lease);
+
+    let lease = pool.lease();
+    assert_eq!(lease.as_ptr(), address, "the buffer was not recycled");
+    assert!(lease.iter().all(|&b| b == 0));
+}
+
+#[test]
+fn blocked_lease_wakes_when_a_lease_is_dropped() {
+    let pool = BufferPool::new(LEN, ALIGN, 1);
+    let mut held = pool.lease();
+    held[0] = 1;
+
+    let (tx, rx) = mpsc::channel();
+    let waiter = thread::spawn({
+        let pool = pool.clone();
+        move || {
+            let lease = pool.lease();
+            tx.send(lease[0]).unwrap();
+        }
+    });
+
+    // The waiter cannot get a buffer while this one is held
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    drop(held);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(0));
+    waiter.join().unwrap();
+    assert_eq!(pool.stats().recycled, 1);
+}
+
+#[test]
+fn clones_share_the_pool() {
+    let pool = BufferPool::new(LEN, ALIGN, 1);
+    let clone = pool.clone();
+    let lease = pool.lease();
+    assert!(clone.try_lease().is_none());
+    drop(lease);
+    assert!(clone.try_lease().is_some());
+}
+
+#[test]
+#[should_panic(expected = "buffer pool capacity must be at least 1")]
+fn zero_capacity_is_rejected() {
+    BufferPool::new(LEN, ALIGN, 0);
+}
+
+#[test]
+#[should_panic(expected = "invalid buffer layout")]
+fn bad_alignment_is_rejected() {
+    BufferPool::new(LEN, 3, 1);
+}
@@ -0,0 +1,120 @@
+// Buffers here are spelled out byte by byte, so the expectations hold on
+// little- and big-endian hosts alike.
+
+use sonic_test::endian::{BeU16, BeU32, BeU64, LeU16, LeU32, LeU64};
+use sonic_test::pubkey::Pubkey;
+use sonic_test::zero_copy_deserialization::{
+    Account, AccountHeader, AccountMut, PackedAccount, PACKED_HEADER_SIZE,
+};
+
+#[test]
+fn wrappers_have_alignment_one() {
+    assert_eq!(align_of::<LeU16>(), 1);
+    assert_eq!(align_of::<LeU32>(), 1);
+    assert_eq!(align_of::<LeU64>(), 1);
+    assert_eq!(align_of::<BeU16>(), 1);
+    assert_eq!(align_of::<BeU32>(), 1);
+    assert_eq!(align_of::<BeU64>(), 1);
+    assert_eq!(align_of::<AccountHeader>(), 1);
+}
+
+#[test]
+fn wrappers_store_declared_byte_order() {
+    assert_eq!(LeU16::new(0x0102).to_bytes(), [0x02, 0x01]);
+    assert_eq!(BeU16::new(0x0102).to_bytes(), [0x01, 0x02]);
+    assert_eq!(LeU32::new(0x0102_0304).to_bytes(), [0x04, 0x03, 0x02, 0x01]);
+    assert_eq!(BeU32::new(0x0102_0304).to_bytes(), [0x01, 0x02, 0x03, 0x04]);
+    assert_eq!(
+        LeU64::new(0x0102_0304_0506_0708).to_bytes(),
+        [0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]
+    );
+    assert_eq!(
+        BeU64::new(0x0102_0304_0506_0708).to_bytes(),
+        [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]
+    );
+}
+
+#[test]
+fn set_rewrites_in_place() {
+    let mut le = LeU32::new(1);
+    le.set(0xAABB_CCDD);
+    assert_eq!(le.get(), 0xAABB_CCDD);
+    assert_eq!(le.to_bytes(), [0xDD, 0xCC, 0xBB, 0xAA]);
+
+    let mut be = BeU64::default();
+    be.set(7);
+    assert_eq!(be.get(), 7);
+    assert_eq!(be.to_bytes(), [0, 0, 0, 0, 0, 0, 0, 7]);
+}
+
+#[test]
+fn foreign_endian_bytes_decode_by_declared_order() {
+    // What a big-endian host writes for a native 0x1122 is what `BeU16`
+    // stores; reading those bytes as `LeU16` swaps them.
+    let big_endian_host = [0x11, 0x22];
+    assert_eq!(BeU16::from_bytes(big_endian_host).get(), 0x1122);
+    assert_eq!(LeU16::from_bytes(big_endian_host).get(), 0x2211);
+
+    let little_endian_host = [0x22, 0x11];
+    assert_eq!(LeU16::from_bytes(little_endian_host).get(), 0x1122);
+}
+
+fn le_account_buffer(amount: [u8; 8], data_len: [u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0u8; AccountHeader::SIZE];
+    bytes[AccountHeader::DISCRIMINATOR_OFFSET] = 3;
+    bytes[AccountHeader::OWNER_OFFSET..AccountHeader::OWNER_OFFSET + 32].fill(0xEE);
+    bytes[AccountHeader::AMOUNT_OFFSET..AccountHeader::AMOUNT_OFFSET + 8].copy_from_slice(&amount);
+    bytes[AccountHeader::DATA_LEN_OFFSET..AccountHeader::DATA_LEN_OFFSET + 4].copy_from_slice(&data_len);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+fn account_header_reads_little_endian_on_any_host() {
+    let bytes = le_account_buffer([0x39, 0x30, 0, 0, 0, 0, 0, 0], [3, 0, 0, 0], b"abc");
+
+    let account = Account::from_bytes(&bytes).unwrap();
+    assert_eq!(account.discriminator(), 3);
+    assert_eq!(account.owner(), &Pubkey::new([0xEE; 32]));
+    assert_eq!(account.amount(), 12345);
+    assert_eq!(account.data(), b"abc");
+}
+
+#[test]
+fn big_endian_host_buffer_is_not_silently_accepted() {
+    // A big-endian host writing native integers would store data_len 3 as
+    // [0, 0, 0, 3], which reads back as 0x0300_0000 and fails validation
+    // instead of misreading the data.
+    let bytes = le_account_buffer([0, 0, 0, 0, 0, 0, 0x30, 0x39], [0, 0, 0, 3], b"abc");
+    assert!(Account::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn account_mut_writes_little_endian() {
+    let mut bytes = le_account_buffer([0; 8], [0; 4], &[0; 8]);
+    let mut account = AccountMut::from_bytes(&mut bytes).unwrap();
+    account.set_amount(0x0102_0304_0506_0708);
+    account.resize_data(2).unwrap();
+
+    let amount = AccountHeader::AMOUNT_OFFSET;
+    assert_eq!(&bytes[amount..amount + 8], &[8, 7, 6, 5, 4, 3, 2, 1]);
+    let data_len = AccountHeader::DATA_LEN_OFFSET;
+    assert_eq!(&bytes[data_len..data_len + 4], &[2, 0, 0, 0]);
+}
+
+#[test]
+fn packed_account_reads_little_endian_at_odd_offsets() {
+    let mut bytes = vec![0xFFu8];
+    bytes.push(9);
+    bytes.extend_from_slice(&[0x44; 32]);
+    bytes.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0x80]);
+    bytes.extend_from_slice(&[2, 0, 0, 0]);
+    bytes.extend_from_slice(b"hi");
+
+    let account = PackedAccount::from_bytes(&bytes[1..]).unwrap();
+    assert_eq!(account.total_len(), PACKED_HEADER_SIZE + 2);
+    assert_eq!(account.discriminator(), 9);
+    assert_eq!(account.amount(), 0x8000_0000_0000_0001);
+    assert_eq!(account.data_len(), 2);
+    assert_eq!(account.data(), b"hi");
+}
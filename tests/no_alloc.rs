@@ -0,0 +1,162 @@
+// Proves the zero-copy parser and the read-only `AccountsDb` paths never
+// allocate. Every test builds its input first and then runs the code under
+// test inside `assert_no_alloc`, which counts allocations on the test's own
+// thread through the `TrackingAlloc` installed below.
+//
+// Needs the `alloc-tracking` feature:
+//
+//     cargo test --features alloc-tracking --test no_alloc
+
+use sonic_test::account_state_management::{AccountState, AccountStateRecord, AccountsDb};
+use sonic_test::pubkey::Pubkey;
+use sonic_test::unsafe_rust_memory_management::AlignedBuffer;
+use sonic_test::unsafe_rust_memory_management::alloc_tracking::{
+    assert_no_alloc, count_allocations, TrackingAlloc,
+};
+use sonic_test::zero_copy_deserialization::constraints::AccountConstraints;
+use sonic_test::zero_copy_deserialization::program_input::ProgramInput;
+use sonic_test::zero_copy_deserialization::{
+    Account, AccountIter, AccountMut, AccountWriter, PackedAccount, ParseError, RECORD_ALIGNMENT,
+};
+
+#[global_allocator]
+static GLOBAL: TrackingAlloc = TrackingAlloc::system();
+
+const OWNER: Pubkey = Pubkey::new([7; 32]);
+const DATA: [u8; 40] = [0xAB; 40];
+
+fn encoded_account() -> AlignedBuffer {
+    let writer = AccountWriter::new().discriminator(3).owner(OWNER).amount(42).slot(9).data(&DATA);
+    let mut buffer = AlignedBuffer::new(writer.encoded_len(), RECORD_ALIGNMENT);
+    writer.write(&mut buffer).unwrap();
+    buffer
+}
+
+fn accounts_db() -> AccountsDb {
+    let db = AccountsDb::new();
+    for i in 1..=3 {
+        db.create_account(Pubkey::new([i; 32]), AccountState::new(u64::from(i) * 100, vec![i; 64], OWNER));
+    }
+    db
+}
+
+#[test]
+fn allocations_are_counted() {
+    // Guards against the tests below passing because nothing is counted
+    let (_, stats) = count_allocations(|| vec![0u8; 100]);
+    assert_eq!(stats.allocations, 1);
+    assert_eq!(stats.bytes, 100);
+}
+
+#[test]
+#[should_panic(expected = "expected no allocations, got 1")]
+fn assert_no_alloc_fails_on_allocation() {
+    assert_no_alloc(|| Box::new(1u64));
+}
+
+#[test]
+fn account_from_bytes_does_not_allocate() {
+    let buffer = encoded_account();
+
+    let (amount, slot, data_len) = assert_no_alloc(|| {
+        let account = Account::from_bytes(&buffer).unwrap();
+        (account.amount(), account.slot(), account.data().len())
+    });
+    assert_eq!((amount, slot, data_len), (42, 9, DATA.len()));
+
+    let constraints = AccountConstraints::new().owner(OWNER).discriminator(3).non_zero_amount();
+    assert_no_alloc(|| Account::from_bytes_with(&buffer, &constraints).unwrap());
+    assert_no_alloc(|| Account::from_bytes_checked(&buffer, &OWNER, 3).unwrap());
+}
+
+#[test]
+fn parse_errors_do_not_allocate() {
+    let buffer = encoded_account();
+
+    let error = assert_no_alloc(|| Account::from_bytes(&buffer[..buffer.len() - 1]).err().unwrap());
+    assert!(matches!(error, ParseError::InvalidDataLength { .. }));
+
+    let wrong_owner = Pubkey::new([8; 32]);
+    let error = assert_no_alloc(|| Account::from_bytes_checked(&buffer, &wrong_owner, 3).err().unwrap());
+    assert!(matches!(error, ParseError::OwnerMismatch { .. }));
+    assert_eq!(assert_no_alloc(|| error.offset()), error.offset());
+}
+
+#[test]
+fn account_mut_updates_do_not_allocate() {
+    let mut buffer = encoded_account();
+
+    assert_no_alloc(|| {
+        let mut account = AccountMut::from_bytes(&mut buffer).unwrap();
+        account.set_amount(7);
+        account.data_mut()[0] = 1;
+    });
+    assert_eq!(Account::from_bytes(&buffer).unwrap().amount(), 7);
+}
+
+#[test]
+fn packed_account_and_writer_do_not_allocate() {
+    let writer = AccountWriter::new().discriminator(1).owner(OWNER).amount(5).data(&DATA);
+    let mut packed = vec![0u8; writer.packed_len()];
+    let mut encoded = AlignedBuffer::new(writer.encoded_len(), RECORD_ALIGNMENT);
+
+    assert_no_alloc(|| {
+        writer.write_packed(&mut packed).unwrap();
+        writer.write(&mut encoded).unwrap();
+    });
+    let amount = assert_no_alloc(|| PackedAccount::from_bytes(&packed).unwrap().amount());
+    assert_eq!(amount, 5);
+}
+
+#[test]
+fn account_iter_over_a_dump_does_not_allocate() {
+    let dump = AlignedBuffer::from_slice(&accounts_db().dump_accounts().unwrap(), RECORD_ALIGNMENT);
+
+    let (count, lamports, data) = assert_no_alloc(|| {
+        let mut count = 0;
+        let mut lamports = 0;
+        let mut data = 0;
+        for record in AccountIter::new(&dump) {
+            let (_, account) = record.unwrap();
+            let state = account.parse_as::<AccountStateRecord>().unwrap();
+            count += 1;
+            lamports += account.amount();
+            data += state.data.len();
+        }
+        (count, lamports, data)
+    });
+    assert_eq!((count, lamports, data), (3, 600, 3 * 64));
+}
+
+#[test]
+fn program_input_parsing_does_not_allocate() {
+    let db = accounts_db();
+    let (alice, bob) = (Pubkey::new([1; 32]), Pubkey::new([2; 32]));
+    let metas = [(alice, true, true), (bob, false, false), (alice, true, true)];
+    let program_id = Pubkey::new([9; 32]);
+    let bytes = db.program_input(&metas, b"transfer", &program_id).unwrap();
+    let bytes = AlignedBuffer::from_slice(&bytes, 8);
+
+    let (entries, lamports) = assert_no_alloc(|| {
+        let input = ProgramInput::from_bytes(&bytes).unwrap();
+        let lamports: u64 = (0..input.num_accounts()).filter_map(|i| input.account(i)).map(|a| a.lamports()).sum();
+        (input.accounts().count(), lamports)
+    });
+    // The duplicate entry resolves to Alice again
+    assert_eq!(entries, 3);
+    assert_eq!(lamports, 100 + 200 + 100);
+}
+
+#[test]
+fn accounts_db_reads_do_not_allocate() {
+    let db = accounts_db();
+    let alice = Pubkey::new([1; 32]);
+
+    let (lamports, data_len) = assert_no_alloc(|| db.with_account(&alice, |a| (a.lamports, a.data.len())).unwrap());
+    assert_eq!((lamports, data_len), (100, 64));
+    assert_no_alloc(|| assert!(db.with_account(&Pubkey::new([4; 32]), |_| ()).is_none()));
+
+    // `get_account` clones the account, data included
+    let (_, stats) = count_allocations(|| db.get_account(&alice));
+    assert_eq!(stats.allocations, 1);
+}
@@ -0,0 +1,68 @@
+// Checks that the library builds with `--no-default-features`, i.e. under
+// `#![no_std]` with only the zero-copy parser, `Pubkey` and the endian
+// wrappers.
+//
+// The host check alone would not notice an accidental `std` import that the
+// host's prelude happens to satisfy, so the library is also checked for a
+// bare-metal target that has no `std` at all. That needs either the target
+// installed (`rustup target add thumbv7em-none-eabi`) or a nightly toolchain
+// with `rust-src` to build `core` from source; without either the target
+// check is skipped with a note, and CI should install the target.
+//
+// The checks use their own target directories so they do not wait on the
+// lock held by the `cargo test` that runs them.
+
+use std::path::Path;
+use std::process::Command;
+
+const NO_STD_TARGET: &str = "thumbv7em-none-eabi";
+
+fn assert_success(command: &mut Command, what: &str) {
+    let output = command.output().expect("failed to run cargo");
+    assert!(output.status.success(), "{what} failed:\n{}", String::from_utf8_lossy(&output.stderr));
+}
+
+fn check<'a>(cargo: &'a mut Command, target_dir: &str) -> &'a mut Command {
+    cargo
+        .args(["check", "--lib", "--no-default-features", "--quiet"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("CARGO_TARGET_DIR", format!("{}/{target_dir}", env!("CARGO_TARGET_TMPDIR")))
+}
+
+// The directory a `rustc --print` command prints, if it exists
+fn rustc_prints_dir(rustc: &mut Command) -> Option<String> {
+    let output = rustc.output().ok().filter(|output| output.status.success())?;
+    let dir = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    Path::new(&dir).is_dir().then_some(dir)
+}
+
+#[test]
+fn library_builds_without_std() {
+    assert_success(check(&mut Command::new(env!("CARGO")), "no_std"), "no_std build");
+}
+
+#[test]
+fn library_builds_for_a_no_std_target() {
+    let installed =
+        rustc_prints_dir(Command::new("rustc").args(["--print", "target-libdir", "--target", NO_STD_TARGET]));
+    if installed.is_some() {
+        let mut cargo = Command::new(env!("CARGO"));
+        check(&mut cargo, "no_std_target").args(["--target", NO_STD_TARGET]);
+        return assert_success(&mut cargo, NO_STD_TARGET);
+    }
+
+    // Fall back to building `core` for the target with a nightly toolchain
+    let nightly_src = rustc_prints_dir(Command::new("rustup").args(["run", "nightly", "rustc", "--print", "sysroot"]))
+        .filter(|sysroot| Path::new(sysroot).join("lib/rustlib/src/rust/library/core").is_dir());
+    if nightly_src.is_none() {
+        eprintln!("skipping: neither the {NO_STD_TARGET} target nor nightly with rust-src is installed");
+        return;
+    }
+    let mut cargo = Command::new("rustup");
+    cargo.args(["run", "nightly", "cargo"]);
+    check(&mut cargo, "no_std_build_std")
+        .args(["--target", NO_STD_TARGET, "-Z", "build-std=core"])
+        .env_remove("RUSTC")
+        .env_remove("RUSTUP_TOOLCHAIN");
+    assert_success(&mut cargo, NO_STD_TARGET);
+}
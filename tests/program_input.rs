@@ -0,0 +1,254 @@
+// Round trips between `serialize_program_input` and `ProgramInput`, and the
+// positioned errors for malformed input.
+
+use sonic_test::account_state_management::{serialize_program_input, AccountState, InstructionAccount};
+use sonic_test::pubkey::Pubkey;
+use sonic_test::zero_copy_deserialization::program_input::{
+    account_entry_len, InputAccount, ProgramInput, SerializedAccountHeader, BPF_ALIGN_OF_U128,
+    DUPLICATE_ENTRY_SIZE, MAX_PERMITTED_DATA_INCREASE,
+};
+use sonic_test::zero_copy_deserialization::ParseError;
+
+const PROGRAM_ID: Pubkey = Pubkey::new([9; 32]);
+const FIRST_ENTRY: usize = 8;
+
+fn account(lamports: u64, data: &[u8], rent_epoch: u64) -> AccountState {
+    AccountState { rent_epoch, ..AccountState::new(lamports, data.to_vec(), Pubkey::new([7; 32])) }
+}
+
+fn entry(pubkey: u8, account: &AccountState, is_signer: bool, is_writable: bool) -> InstructionAccount<'_> {
+    InstructionAccount { pubkey: Pubkey::new([pubkey; 32]), account, is_signer, is_writable }
+}
+
+// Alice, Bob, then Alice again as a duplicate of entry 0
+fn sample() -> Vec<u8> {
+    let alice = account(100, b"alice", 3);
+    let bob = AccountState { executable: true, ..account(200, b"bob's data", 4) };
+    serialize_program_input(
+        &[entry(1, &alice, true, true), entry(2, &bob, false, false), entry(1, &alice, true, true)],
+        b"transfer",
+        &PROGRAM_ID,
+    )
+    .unwrap()
+}
+
+// One account with `data`, so offsets are easy to compute
+fn single(data: &[u8], instruction_data: &[u8]) -> Vec<u8> {
+    let alice = account(100, data, 3);
+    serialize_program_input(&[entry(1, &alice, true, true)], instruction_data, &PROGRAM_ID).unwrap()
+}
+
+#[test]
+fn round_trips_accounts_and_duplicates() {
+    let bytes = sample();
+    let input = ProgramInput::from_bytes(&bytes).unwrap();
+
+    assert_eq!(input.num_accounts(), 3);
+    assert_eq!(input.instruction_data(), b"transfer");
+    assert_eq!(input.program_id(), &PROGRAM_ID);
+
+    let entries: Vec<_> = input.accounts().collect();
+    let InputAccount::Account(alice) = entries[0] else { panic!("entry 0 is a duplicate") };
+    assert_eq!(alice.key(), &Pubkey::new([1; 32]));
+    assert_eq!(alice.owner(), &Pubkey::new([7; 32]));
+    assert_eq!((alice.lamports(), alice.data(), alice.rent_epoch()), (100, &b"alice"[..], 3));
+    assert!(alice.is_signer() && alice.is_writable() && !alice.executable());
+    assert_eq!(alice.original_data_len(), 5);
+
+    let InputAccount::Account(bob) = entries[1] else { panic!("entry 1 is a duplicate") };
+    assert_eq!((bob.lamports(), bob.data(), bob.rent_epoch()), (200, &b"bob's data"[..], 4));
+    assert!(!bob.is_signer() && !bob.is_writable() && bob.executable());
+    assert_eq!(bob.original_data_len(), 10);
+
+    assert!(matches!(entries[2], InputAccount::Duplicate(0)));
+    assert_eq!(input.account(2).unwrap().key(), alice.key());
+    assert!(input.account(3).is_none());
+}
+
+#[test]
+fn entries_reserve_realloc_space_and_align_rent_epoch() {
+    for data_len in 0..=2 * BPF_ALIGN_OF_U128 {
+        let data = vec![0xAA; data_len];
+        let bytes = single(&data, b"");
+
+        let data_start = FIRST_ENTRY + SerializedAccountHeader::SIZE;
+        let entry_len = account_entry_len(FIRST_ENTRY, data_len);
+        let rent_epoch = FIRST_ENTRY + entry_len - 8;
+        assert_eq!(rent_epoch % BPF_ALIGN_OF_U128, 0, "data_len {data_len}");
+        assert!(rent_epoch - data_start - data_len >= MAX_PERMITTED_DATA_INCREASE);
+        assert!(rent_epoch - data_start - data_len < MAX_PERMITTED_DATA_INCREASE + BPF_ALIGN_OF_U128);
+
+        // Realloc space and padding are zeroed
+        assert!(bytes[data_start + data_len..rent_epoch].iter().all(|&b| b == 0));
+        assert_eq!(bytes[rent_epoch..rent_epoch + 8], 3u64.to_le_bytes());
+        // Followed by the empty instruction data and the program id
+        assert_eq!(bytes.len(), FIRST_ENTRY + entry_len + 8 + 32);
+
+        let input = ProgramInput::from_bytes(&bytes).unwrap();
+        assert_eq!(input.account(0).unwrap().data(), &data[..]);
+        assert_eq!(input.account(0).unwrap().rent_epoch(), 3);
+    }
+}
+
+#[test]
+fn entries_after_a_duplicate_stay_aligned() {
+    let alice = account(1, b"abc", 0);
+    let bob = account(2, b"", 0);
+    let bytes = serialize_program_input(
+        &[entry(1, &alice, false, false), entry(1, &alice, false, false), entry(2, &bob, false, false)],
+        b"",
+        &PROGRAM_ID,
+    )
+    .unwrap();
+
+    let bob_offset = FIRST_ENTRY + account_entry_len(FIRST_ENTRY, 3) + DUPLICATE_ENTRY_SIZE;
+    assert_eq!(bob_offset % BPF_ALIGN_OF_U128, 0);
+    assert_eq!(bytes[bob_offset + 8..bob_offset + 40], [2; 32]);
+    assert_eq!(ProgramInput::from_bytes(&bytes).unwrap().account(2).unwrap().lamports(), 2);
+}
+
+fn error(bytes: &[u8]) -> ParseError {
+    ProgramInput::from_bytes(bytes).unwrap_err()
+}
+
+#[test]
+fn rejects_duplicates_that_do_not_point_back() {
+    let mut bytes = sample();
+    let third = FIRST_ENTRY + account_entry_len(FIRST_ENTRY, 5);
+    let third = third + account_entry_len(third, 10);
+
+    // Pointing at itself
+    bytes[third] = 2;
+    assert_eq!(error(&bytes), ParseError::InvalidValue { field: "dup_info", offset: third });
+
+    // Pointing forward
+    bytes[FIRST_ENTRY..FIRST_ENTRY + 8].copy_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(error(&bytes), ParseError::InvalidValue { field: "dup_info", offset: FIRST_ENTRY });
+}
+
+#[test]
+fn rejects_invalid_flags() {
+    let mut bytes = single(b"abc", b"");
+    let offset = FIRST_ENTRY + SerializedAccountHeader::IS_WRITABLE_OFFSET;
+    bytes[offset] = 2;
+    assert_eq!(error(&bytes), ParseError::InvalidValue { field: "is_writable", offset });
+}
+
+#[test]
+fn rejects_data_longer_than_the_buffer() {
+    let mut bytes = single(b"abc", b"");
+    let data_len = FIRST_ENTRY + SerializedAccountHeader::DATA_LEN_OFFSET;
+    bytes[data_len..data_len + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    let data_start = FIRST_ENTRY + SerializedAccountHeader::SIZE;
+    assert_eq!(
+        error(&bytes),
+        ParseError::InvalidDataLength {
+            field: "data_len",
+            offset: data_start,
+            length: usize::MAX,
+            remaining: bytes.len() - data_start,
+        }
+    );
+}
+
+#[test]
+fn rejects_truncated_rent_epoch() {
+    let bytes = single(b"abc", b"");
+    let rent_epoch = FIRST_ENTRY + account_entry_len(FIRST_ENTRY, 3) - 8;
+    assert_eq!(
+        error(&bytes[..rent_epoch + 5]),
+        ParseError::InsufficientData { field: "rent_epoch", offset: rent_epoch, expected: 8, actual: 5 }
+    );
+}
+
+#[test]
+fn rejects_truncated_instruction_data() {
+    let bytes = single(b"abc", b"transfer");
+    let len_offset = FIRST_ENTRY + account_entry_len(FIRST_ENTRY, 3);
+    let data_offset = len_offset + 8;
+    assert_eq!(
+        error(&bytes[..data_offset + 3]),
+        ParseError::InvalidDataLength {
+            field: "instruction_data_len",
+            offset: data_offset,
+            length: 8,
+            remaining: 3,
+        }
+    );
+
+    // The program id is missing entirely
+    let program_id = data_offset + 8;
+    assert_eq!(
+        error(&bytes[..program_id]),
+        ParseError::InsufficientData { field: "program_id", offset: program_id, expected: 32, actual: 0 }
+    );
+}
+
+#[test]
+fn rejects_oversized_num_accounts() {
+    let mut bytes = single(b"abc", b"");
+    bytes[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    // The bytes after the only account are read as more entries: the zero
+    // instruction data length is a duplicate of entry 0, and the first byte
+    // of the program id is a duplicate of an entry that does not exist yet
+    let instruction_data_len = FIRST_ENTRY + account_entry_len(FIRST_ENTRY, 3);
+    assert_eq!(
+        error(&bytes),
+        ParseError::InvalidValue { field: "dup_info", offset: instruction_data_len + DUPLICATE_ENTRY_SIZE }
+    );
+
+    let mut empty = [0u8; 8];
+    empty.copy_from_slice(&u64::MAX.to_le_bytes());
+    assert_eq!(
+        ProgramInput::from_bytes(&empty).unwrap_err(),
+        ParseError::InsufficientData { field: "dup_info", offset: 8, expected: 1, actual: 0 }
+    );
+}
+
+#[test]
+fn every_truncation_is_an_error() {
+    let bytes = sample();
+    for len in 0..bytes.len() {
+        assert!(ProgramInput::from_bytes(&bytes[..len]).is_err(), "prefix of {len} bytes parsed");
+    }
+    assert!(ProgramInput::from_bytes(&bytes).is_ok());
+}
+
+#[test]
+fn rejects_duplicates_that_dup_info_cannot_hold() {
+    let accounts: Vec<_> = (0..=255u8).map(|i| account(i.into(), b"", 0)).collect();
+    let mut entries: Vec<_> = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, account)| InstructionAccount { pubkey: Pubkey::new([i as u8; 32]), account, is_signer: false, is_writable: false })
+        .collect();
+
+    // Index 254 is the last one `dup_info` can hold, as 0xFF marks a non-duplicate
+    entries.push(entries[254]);
+    let bytes = serialize_program_input(&entries, b"", &PROGRAM_ID).unwrap();
+    assert_eq!(ProgramInput::from_bytes(&bytes).unwrap().account(256).unwrap().lamports(), 254);
+
+    entries.push(entries[255]);
+    let duplicate = bytes.len() - 8 - 32;
+    assert_eq!(
+        serialize_program_input(&entries, b"", &PROGRAM_ID).unwrap_err(),
+        ParseError::InvalidValue { field: "dup_info", offset: duplicate }
+    );
+}
+
+#[test]
+fn rejects_data_longer_than_u32() {
+    // Zeroed allocations are mapped lazily, so this does not touch 4 GiB
+    let huge = AccountState { data: vec![0; u32::MAX as usize + 1], ..account(1, b"", 0) };
+    assert_eq!(
+        serialize_program_input(&[entry(1, &huge, false, false)], b"", &PROGRAM_ID).unwrap_err(),
+        ParseError::DataLengthOutOfRange {
+            offset: FIRST_ENTRY + SerializedAccountHeader::ORIGINAL_DATA_LEN_OFFSET,
+            length: u32::MAX as usize + 1,
+            min: 0,
+            max: u32::MAX as usize,
+        }
+    );
+}
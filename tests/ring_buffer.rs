@@ -0,0 +1,122 @@
+// Blocking `send`/`recv` on real threads. Each test keeps one side waiting
+// well past the spin and yield budget, so it is parked when the other side
+// acts and only returns if that side wakes it.
+
+use std::thread;
+use std::time::Duration;
+
+use sonic_test::unsafe_rust_memory_management::ring_buffer::{mpmc, spsc, RecvError, SendError};
+
+// Long enough for a blocked call to give up spinning and park
+const PARKED: Duration = Duration::from_millis(50);
+
+#[test]
+fn spsc_send_wakes_a_parked_receiver() {
+    let (tx, rx) = spsc::channel(1);
+    let consumer = thread::spawn(move || rx.recv());
+
+    thread::sleep(PARKED);
+    tx.send(7).unwrap();
+    assert_eq!(consumer.join().unwrap(), Ok(7));
+}
+
+#[test]
+fn spsc_recv_wakes_a_parked_sender() {
+    let (tx, rx) = spsc::channel(1);
+    tx.send(1).unwrap();
+    let producer = thread::spawn(move || tx.send(2));
+
+    thread::sleep(PARKED);
+    assert_eq!(rx.recv(), Ok(1));
+    assert_eq!(producer.join().unwrap(), Ok(()));
+    assert_eq!(rx.recv(), Ok(2));
+}
+
+#[test]
+fn spsc_disconnect_wakes_the_parked_side() {
+    let (tx, rx) = spsc::channel::<u32>(1);
+    let consumer = thread::spawn(move || rx.recv());
+    thread::sleep(PARKED);
+    drop(tx);
+    assert_eq!(consumer.join().unwrap(), Err(RecvError));
+
+    let (tx, rx) = spsc::channel(1);
+    tx.send(1).unwrap();
+    let producer = thread::spawn(move || tx.send(2));
+    thread::sleep(PARKED);
+    drop(rx);
+    assert_eq!(producer.join().unwrap(), Err(SendError(2)));
+}
+
+#[test]
+fn mpmc_wakes_every_parked_receiver() {
+    let (tx, rx) = mpmc::channel(4);
+    let consumers: Vec<_> = (0..3)
+        .map(|_| {
+            let rx = rx.clone();
+            thread::spawn(move || rx.recv())
+        })
+        .collect();
+
+    thread::sleep(PARKED);
+    tx.send(1).unwrap();
+    tx.send(2).unwrap();
+    drop(tx);
+
+    let results: Vec<_> = consumers.into_iter().map(|consumer| consumer.join().unwrap()).collect();
+    let mut received: Vec<_> = results.iter().filter_map(|result| result.ok()).collect();
+    received.sort();
+    assert_eq!(received, [1, 2]);
+    assert!(results.contains(&Err(RecvError)));
+}
+
+#[test]
+fn mpmc_recv_wakes_parked_senders() {
+    let (tx, rx) = mpmc::channel(1);
+    tx.send(0).unwrap();
+    let producers: Vec<_> = (1..=2)
+        .map(|i| {
+            let tx = tx.clone();
+            thread::spawn(move || tx.send(i))
+        })
+        .collect();
+
+    thread::sleep(PARKED);
+    let mut received: Vec<_> = (0..3).map(|_| rx.recv().unwrap()).collect();
+    for producer in producers {
+        assert_eq!(producer.join().unwrap(), Ok(()));
+    }
+    received.sort();
+    assert_eq!(received, [0, 1, 2]);
+}
+
+#[test]
+fn mpmc_blocking_pipeline_delivers_everything() {
+    // Small capacity keeps both sides parking and waking throughout
+    let (tx, rx) = mpmc::channel(2);
+    let producers: Vec<_> = (0..4)
+        .map(|p| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for i in 0..1_000 {
+                    tx.send(p * 1_000 + i).unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+    let consumers: Vec<_> = (0..4)
+        .map(|_| {
+            let rx = rx.clone();
+            thread::spawn(move || std::iter::from_fn(|| rx.recv().ok()).collect::<Vec<u32>>())
+        })
+        .collect();
+    drop(rx);
+
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    let mut received: Vec<u32> = consumers.into_iter().flat_map(|consumer| consumer.join().unwrap()).collect();
+    received.sort_unstable();
+    assert_eq!(received, (0..4_000).collect::<Vec<_>>());
+}
@@ -0,0 +1,192 @@
+// Exhaustive interleaving tests for the ring buffer channels. They only build
+// with the `loom` cfg, which swaps the channels' atomics for loom's model
+// checked ones:
+//
+//     RUSTFLAGS="--cfg loom" cargo test --release --test ring_buffer_loom
+//
+// Each model is kept to two or three threads and a handful of operations, and
+// explores every ordering with up to three preemptions, which is where loom's
+// own guidance puts the bugs it finds in practice.
+
+#![cfg(loom)]
+
+use loom::model::Builder;
+use loom::thread;
+
+use sonic_test::unsafe_rust_memory_management::ring_buffer::{
+    RecvError, SendError, TryRecvError, TrySendError, mpmc, spsc,
+};
+
+fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = Builder::new();
+    builder.preemption_bound.get_or_insert(3);
+    builder.check(f);
+}
+
+#[test]
+fn spsc_delivers_in_order_across_wraparound() {
+    model(|| {
+        let (tx, rx) = spsc::channel(2);
+        let producer = thread::spawn(move || {
+            for i in 0..3 {
+                tx.send(i).unwrap();
+            }
+        });
+
+        for i in 0..3 {
+            assert_eq!(rx.recv(), Ok(i));
+        }
+        assert_eq!(rx.recv(), Err(RecvError));
+        producer.join().unwrap();
+    });
+}
+
+#[test]
+fn spsc_last_value_survives_sender_drop() {
+    model(|| {
+        let (tx, rx) = spsc::channel(1);
+        let producer = thread::spawn(move || {
+            tx.try_send(7).unwrap();
+        });
+
+        // Whatever the interleaving, a receiver that sees the sender gone has
+        // already been able to see the value
+        let mut received = Vec::new();
+        loop {
+            match rx.try_recv() {
+                Ok(value) => received.push(value),
+                Err(TryRecvError::Empty) => thread::yield_now(),
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        assert_eq!(received, [7]);
+        producer.join().unwrap();
+    });
+}
+
+#[test]
+fn spsc_full_channel_signals_backpressure() {
+    model(|| {
+        let (tx, rx) = spsc::channel(1);
+        tx.try_send(1).unwrap();
+        let consumer = thread::spawn(move || rx.recv().unwrap());
+
+        // Either the consumer has not freed the slot yet and the value comes
+        // back, or it has and the send succeeds
+        match tx.try_send(2) {
+            Ok(()) => assert_eq!(tx.len(), 1),
+            Err(TrySendError::Full(value)) => assert_eq!(value, 2),
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+        assert_eq!(consumer.join().unwrap(), 1);
+    });
+}
+
+#[test]
+fn spsc_send_fails_after_receiver_drop() {
+    model(|| {
+        let (tx, rx) = spsc::channel::<u32>(1);
+        let consumer = thread::spawn(move || drop(rx));
+        consumer.join().unwrap();
+        assert_eq!(tx.send(3), Err(SendError(3)));
+    });
+}
+
+// Under loom a blocked call parks at once, and loom reports a deadlock if
+// every thread ends up parked, so these catch lost wake-ups
+#[test]
+fn spsc_parked_sides_wake_on_disconnect() {
+    model(|| {
+        let (tx, rx) = spsc::channel::<u32>(1);
+        let consumer = thread::spawn(move || rx.recv());
+        drop(tx);
+        assert_eq!(consumer.join().unwrap(), Err(RecvError));
+    });
+}
+
+#[test]
+fn spsc_recv_wakes_a_parked_sender() {
+    model(|| {
+        let (tx, rx) = spsc::channel(1);
+        tx.send(1).unwrap();
+        let producer = thread::spawn(move || tx.send(2));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        assert_eq!(producer.join().unwrap(), Ok(()));
+    });
+}
+
+#[test]
+fn mpmc_two_producers_deliver_every_value_once() {
+    model(|| {
+        let (tx, rx) = mpmc::channel(2);
+        let producers: Vec<_> = [1, 2]
+            .into_iter()
+            .map(|value| {
+                let tx = tx.clone();
+                thread::spawn(move || tx.send(value).unwrap())
+            })
+            .collect();
+        drop(tx);
+
+        let mut received = [rx.recv().unwrap(), rx.recv().unwrap()];
+        received.sort();
+        assert_eq!(received, [1, 2]);
+        assert_eq!(rx.recv(), Err(RecvError));
+        for producer in producers {
+            producer.join().unwrap();
+        }
+    });
+}
+
+#[test]
+fn mpmc_two_consumers_take_every_value_once() {
+    model(|| {
+        let (tx, rx) = mpmc::channel(2);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+
+        let other = rx.clone();
+        let consumer = thread::spawn(move || other.recv().unwrap());
+        let mine = rx.recv().unwrap();
+        let theirs = consumer.join().unwrap();
+
+        assert_eq!(mine + theirs, 3);
+        assert_ne!(mine, theirs);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    });
+}
+
+#[test]
+fn mpmc_slot_is_reused_after_wraparound() {
+    model(|| {
+        let (tx, rx) = mpmc::channel(1);
+        let producer = thread::spawn(move || {
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+        });
+
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        producer.join().unwrap();
+    });
+}
+
+#[test]
+fn undelivered_values_are_dropped_with_the_channel() {
+    model(|| {
+        let value = loom::sync::Arc::new(());
+        let (tx, rx) = mpmc::channel(2);
+        let sent = value.clone();
+        let producer = thread::spawn(move || tx.send(sent).unwrap());
+        producer.join().unwrap();
+        drop(rx);
+        assert_eq!(loom::sync::Arc::strong_count(&value), 1);
+
+        let (tx, rx) = spsc::channel(2);
+        tx.send(value.clone()).unwrap();
+        drop((tx, rx));
+        assert_eq!(loom::sync::Arc::strong_count(&value), 1);
+    });
+}
@@ -0,0 +1,134 @@
+// Concurrency tests for `SharedBufferMut`. Run them under Miri to check for
+// data races and aliasing violations:
+//
+//     cargo +nightly miri test --test shared_buffer
+//
+// Thread and iteration counts are kept small so Miri finishes quickly.
+
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use sonic_test::unsafe_rust_memory_management::{ReserveError, SharedBufferMut};
+
+const THREADS: usize = 4;
+const CHUNK: usize = 16;
+
+#[test]
+fn threads_write_disjoint_ranges() {
+    let mut buffer = SharedBufferMut::new(THREADS * CHUNK);
+
+    thread::scope(|scope| {
+        for i in 0..THREADS {
+            let buffer = &buffer;
+            scope.spawn(move || {
+                let mut range = buffer.reserve(i * CHUNK..(i + 1) * CHUNK).unwrap();
+                for (j, byte) in range.iter_mut().enumerate() {
+                    *byte = (i * CHUNK + j) as u8;
+                }
+            });
+        }
+    });
+
+    let expected: Vec<u8> = (0..THREADS * CHUNK).map(|i| i as u8).collect();
+    assert_eq!(buffer.as_mut_slice(), &expected[..]);
+}
+
+#[test]
+fn threads_write_interleaved_ranges_at_the_same_time() {
+    // Every thread holds its reservation until all of them have written, so
+    // the writes genuinely overlap in time
+    let buffer = Arc::new(SharedBufferMut::new(THREADS * CHUNK));
+    let barrier = Arc::new(Barrier::new(THREADS));
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|i| {
+            let buffer = Arc::clone(&buffer);
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                let mut range = buffer.reserve(i * CHUNK..(i + 1) * CHUNK).unwrap();
+                range.fill(i as u8 + 1);
+                barrier.wait();
+                assert!(range.iter().all(|&b| b == i as u8 + 1));
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    for i in 0..THREADS * CHUNK {
+        assert_eq!(buffer.get(i), Ok((i / CHUNK) as u8 + 1));
+    }
+}
+
+#[test]
+fn contended_range_is_handed_over_without_races() {
+    // All threads increment the same counter byte, retrying until they win
+    // the reservation. Lost updates or a race would show up as a wrong total
+    // (or as a Miri error).
+    let buffer = SharedBufferMut::new(1);
+    let increments = if cfg!(miri) { 5 } else { 60 };
+
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..increments {
+                    let mut counter = loop {
+                        match buffer.reserve(0..1) {
+                            Ok(counter) => break counter,
+                            Err(ReserveError::Overlap { .. }) => thread::yield_now(),
+                            Err(e) => panic!("unexpected error: {e}"),
+                        }
+                    };
+                    counter[0] += 1;
+                }
+            });
+        }
+    });
+
+    assert_eq!(buffer.get(0), Ok((THREADS * increments) as u8));
+}
+
+#[test]
+fn overlapping_reservation_is_refused_until_released() {
+    let buffer = SharedBufferMut::new(32);
+
+    let held = buffer.reserve(8..16).unwrap();
+    assert_eq!(buffer.reserve(0..9).unwrap_err(), ReserveError::Overlap { start: 0, end: 9 });
+    assert_eq!(buffer.reserve(15..20).unwrap_err(), ReserveError::Overlap { start: 15, end: 20 });
+    assert_eq!(buffer.get(10), Err(ReserveError::Overlap { start: 10, end: 11 }));
+
+    // Adjacent ranges do not overlap
+    assert!(buffer.reserve(0..8).is_ok());
+    assert!(buffer.reserve(16..32).is_ok());
+
+    drop(held);
+    assert!(buffer.reserve(0..32).is_ok());
+}
+
+#[test]
+fn reservation_can_move_to_another_thread() {
+    let buffer = SharedBufferMut::new(8);
+    let mut range = buffer.reserve(2..6).unwrap();
+
+    thread::scope(|scope| {
+        scope.spawn(move || range.copy_from_slice(b"ping"));
+    });
+
+    assert_eq!(buffer.reserve(0..8).unwrap().as_ref(), b"\0\0ping\0\0");
+}
+
+#[test]
+fn invalid_ranges_are_rejected() {
+    let buffer = SharedBufferMut::new(4);
+
+    assert_eq!(buffer.reserve(2..5).unwrap_err(), ReserveError::OutOfBounds { start: 2, end: 5, len: 4 });
+    #[allow(clippy::reversed_empty_ranges)]
+    let inverted = buffer.reserve(3..1).unwrap_err();
+    assert_eq!(inverted, ReserveError::OutOfBounds { start: 3, end: 1, len: 4 });
+    assert!(buffer.get(4).is_err());
+
+    // Empty ranges never conflict
+    let _whole = buffer.reserve(0..4).unwrap();
+    assert!(buffer.reserve(2..2).unwrap().is_empty());
+}
@@ -0,0 +1,202 @@
+// Two-process test for `SharedMemoryBuffer`. The test re-runs its own binary
+// as a reader process, hands it the buffer's descriptor over a Unix socket,
+// and keeps rewriting the buffer while the reader checks that every snapshot
+// it takes comes from a single write.
+
+#![cfg(target_os = "linux")]
+
+use std::env;
+use std::fs::OpenOptions;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::ptr;
+use std::process::Command;
+use std::thread;
+
+use sonic_test::unsafe_rust_memory_management::shared_memory::{
+    Access, SharedMemoryBuffer, SharedMemoryError,
+};
+
+const LEN: usize = 4096;
+const ROUNDS: u8 = 200;
+const SOCKET_VAR: &str = "SONIC_TEST_SHM_SOCKET";
+
+// A write of round `n` fills the data with `n`, so a snapshot holding more
+// than one value is torn
+fn snapshot_round(snapshot: &[u8]) -> u8 {
+    let round = snapshot[0];
+    assert!(snapshot.iter().all(|&b| b == round), "torn snapshot: starts with {round}");
+    round
+}
+
+#[test]
+fn reader_process_never_sees_torn_writes() {
+    let dir = env::temp_dir().join(format!("sonic-test-shm-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let socket_path = dir.join("socket");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+
+    let reader = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "reader_process", "--ignored", "--nocapture"])
+        .env(SOCKET_VAR, &socket_path)
+        .spawn()
+        .unwrap();
+
+    let buffer = SharedMemoryBuffer::create("sonic-test-accounts", LEN).unwrap();
+    let (socket, _) = listener.accept().unwrap();
+    buffer.send_to(&socket, Access::ReadOnly).unwrap();
+
+    let mut data = [0u8; LEN];
+    for round in 1..=ROUNDS {
+        data.fill(round);
+        buffer.write(0, &data).unwrap();
+        thread::yield_now();
+    }
+    assert_eq!(buffer.sequence(), 2 * ROUNDS as u64);
+
+    let status = reader.wait_with_output().unwrap().status;
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(status.success(), "reader process failed: {status}");
+}
+
+// Runs in the child process started by the test above
+#[test]
+#[ignore = "started by reader_process_never_sees_torn_writes"]
+fn reader_process() {
+    let Some(socket_path) = env::var_os(SOCKET_VAR) else {
+        return;
+    };
+    let socket = UnixStream::connect(socket_path).unwrap();
+    let buffer = SharedMemoryBuffer::receive(&socket, Access::ReadOnly).unwrap();
+    assert_eq!(buffer.len(), LEN);
+
+    // The read-only mapping refuses writes instead of faulting, and the
+    // descriptor it came with cannot be mapped read-write
+    assert!(matches!(buffer.write(0, b"x"), Err(SharedMemoryError::ReadOnly)));
+    assert!(!maps_writable(&buffer));
+
+    let mut snapshot = [0u8; LEN];
+    let mut last = 0;
+    while last < ROUNDS {
+        let sequence = buffer.read(0, &mut snapshot).unwrap();
+        let round = snapshot_round(&snapshot);
+        // Rounds only move forward and match the sequence they were read at
+        assert!(round >= last, "went back from round {last} to {round}");
+        assert_eq!(sequence, 2 * round as u64);
+        last = round;
+    }
+}
+
+#[test]
+fn threads_never_see_torn_writes() {
+    let buffer = SharedMemoryBuffer::create("sonic-test-threads", 256).unwrap();
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for round in 1..=ROUNDS {
+                buffer.write(0, &[round; 256]).unwrap();
+            }
+        });
+        for _ in 0..2 {
+            scope.spawn(|| {
+                // A failed `try_read` may leave a torn copy behind, so only
+                // successful snapshots are checked
+                let mut snapshot = [0u8; 256];
+                let mut round = 0;
+                while round < ROUNDS {
+                    match buffer.try_read(0, &mut snapshot) {
+                        Ok(_) => round = snapshot_round(&snapshot),
+                        Err(SharedMemoryError::TornRead) => thread::yield_now(),
+                        Err(e) => panic!("unexpected error: {e}"),
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn open_validates_the_descriptor() {
+    let buffer = SharedMemoryBuffer::create("sonic-test-open", 16).unwrap();
+    buffer.write(4, b"data").unwrap();
+
+    // A second mapping of the same file sees the same bytes
+    let fd = buffer.try_clone_read_only().unwrap();
+    let reader = SharedMemoryBuffer::open(fd, Access::ReadOnly).unwrap();
+    let mut out = [0u8; 8];
+    assert_eq!(reader.read(2, &mut out).unwrap(), 2);
+    assert_eq!(&out, b"\0\0data\0\0");
+
+    assert!(matches!(
+        reader.read(12, &mut out),
+        Err(SharedMemoryError::OutOfBounds { start: 12, end: 20, len: 16 })
+    ));
+
+    // A regular file has no seals
+    let file = std::fs::File::open(env::current_exe().unwrap()).unwrap();
+    assert!(SharedMemoryBuffer::open(file.into(), Access::ReadOnly).is_err());
+}
+
+// Whether the kernel lets `buffer`'s descriptor be mapped shared and
+// writable, bypassing the checks in `SharedMemoryBuffer::open`
+fn maps_writable(buffer: &impl AsFd) -> bool {
+    let fd = buffer.as_fd().as_raw_fd();
+    let prot = libc::PROT_READ | libc::PROT_WRITE;
+    let addr = unsafe { libc::mmap(ptr::null_mut(), 4096, prot, libc::MAP_SHARED, fd, 0) };
+    if addr == libc::MAP_FAILED {
+        return false;
+    }
+    unsafe { libc::munmap(addr, 4096) };
+    true
+}
+
+// A read-write descriptor for the same file, as any process could open
+// through `/proc/<pid>/fd`
+fn reopen_read_write(buffer: &SharedMemoryBuffer) -> OwnedFd {
+    let path = format!("/proc/self/fd/{}", buffer.as_fd().as_raw_fd());
+    OpenOptions::new().read(true).write(true).open(path).unwrap().into()
+}
+
+#[test]
+fn read_only_peers_get_a_read_only_descriptor() {
+    let buffer = SharedMemoryBuffer::create("sonic-test-send", 16).unwrap();
+    buffer.write(0, b"data").unwrap();
+    let (ours, theirs) = UnixStream::pair().unwrap();
+
+    buffer.send_to(&ours, Access::ReadOnly).unwrap();
+    let fd = SharedMemoryBuffer::receive(&theirs, Access::ReadOnly).unwrap().as_fd().try_clone_to_owned().unwrap();
+    assert!(!maps_writable(&fd));
+    assert!(matches!(SharedMemoryBuffer::open(fd, Access::ReadWrite), Err(SharedMemoryError::ReadOnly)));
+
+    buffer.send_to(&ours, Access::ReadOnly).unwrap();
+    assert!(matches!(SharedMemoryBuffer::receive(&theirs, Access::ReadWrite), Err(SharedMemoryError::ReadOnly)));
+
+    // A read-write peer can write, and the creator sees it
+    buffer.send_to(&ours, Access::ReadWrite).unwrap();
+    let peer = SharedMemoryBuffer::receive(&theirs, Access::ReadWrite).unwrap();
+    peer.write(0, b"peer").unwrap();
+    let mut out = [0u8; 4];
+    buffer.read(0, &mut out).unwrap();
+    assert_eq!(&out, b"peer");
+}
+
+#[test]
+fn exclusive_writer_seals_out_every_other_writer() {
+    let buffer = SharedMemoryBuffer::create_exclusive_writer("sonic-test-exclusive", 16).unwrap();
+    buffer.write(0, b"data").unwrap();
+
+    // Even a descriptor opened read-write cannot make a writable mapping
+    let fd = reopen_read_write(&buffer);
+    assert!(!maps_writable(&fd));
+    assert!(matches!(SharedMemoryBuffer::open(fd, Access::ReadWrite), Err(SharedMemoryError::ReadOnly)));
+
+    let reader = SharedMemoryBuffer::open(reopen_read_write(&buffer), Access::ReadOnly).unwrap();
+    buffer.write(4, b"more").unwrap();
+    let mut out = [0u8; 8];
+    assert_eq!(reader.read(0, &mut out).unwrap(), 4);
+    assert_eq!(&out, b"datamore");
+
+    // A plain buffer still allows it
+    let shared = SharedMemoryBuffer::create("sonic-test-shared", 16).unwrap();
+    assert!(maps_writable(&reopen_read_write(&shared)));
+}
@@ -0,0 +1,131 @@
+// `#[derive(ZeroCopy)]` for plain structs that are overlaid directly on
+// account bytes.
+//
+// For a struct `T` the derive generates:
+// - `T::SIZE` and a `<FIELD>_OFFSET` constant for every field whose name does
+//   not start with `_` (explicit padding fields are skipped)
+// - compile-time assertions that every field is `Pod` and that the struct has
+//   no implicit padding, i.e. its size equals the sum of its field sizes
+// - `T::from_bytes` / `T::from_bytes_mut`, which check length and alignment
+//   and return `ParseError::InsufficientData` / `ParseError::InvalidAlignment`
+//   naming `T` as the field
+// - an `unsafe impl Pod for T`, so derived structs can be nested
+//
+// The generated code refers to the runtime crate as `::sonic_test`.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Error, Fields, parse_macro_input};
+
+#[proc_macro_derive(ZeroCopy)]
+pub fn derive_zero_copy(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "ZeroCopy cannot be derived for generic structs",
+        ));
+    }
+
+    if !has_stable_repr(&input)? {
+        return Err(Error::new(
+            Span::call_site(),
+            "ZeroCopy requires #[repr(C)] or #[repr(transparent)]",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &data.fields,
+                    "ZeroCopy requires a struct with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "ZeroCopy can only be derived for structs",
+            ));
+        }
+    };
+
+    let field_types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+
+    let offsets = fields.iter().filter_map(|field| {
+        let ident = field.ident.as_ref()?;
+        let field_name = ident.to_string();
+        if field_name.starts_with('_') {
+            return None;
+        }
+        let const_name = format_ident!("{}_OFFSET", field_name.to_uppercase());
+        Some(quote! {
+            pub const #const_name: usize = ::core::mem::offset_of!(#name, #ident);
+        })
+    });
+
+    let type_name = name.to_string();
+    let padding_message = format!("{name} has implicit padding; add explicit padding fields");
+
+    Ok(quote! {
+        impl #name {
+            pub const SIZE: usize = ::core::mem::size_of::<#name>();
+            #(#offsets)*
+
+            pub fn from_bytes(bytes: &[u8]) -> ::core::result::Result<&Self, ::sonic_test::zero_copy_deserialization::ParseError> {
+                ::sonic_test::zero_copy_deserialization::check_overlay::<Self>(bytes, #type_name)?;
+                // Length and alignment were checked by `check_overlay`, and
+                // `Self` is `Pod` with no padding, so any bytes are valid
+                Ok(unsafe { &*(bytes.as_ptr() as *const Self) })
+            }
+
+            pub fn from_bytes_mut(bytes: &mut [u8]) -> ::core::result::Result<&mut Self, ::sonic_test::zero_copy_deserialization::ParseError> {
+                ::sonic_test::zero_copy_deserialization::check_overlay::<Self>(bytes, #type_name)?;
+                // As above; without padding, writes through the reference
+                // cannot leave uninitialized bytes in the slice
+                Ok(unsafe { &mut *(bytes.as_mut_ptr() as *mut Self) })
+            }
+        }
+
+        const _: () = {
+            const fn assert_pod<T: ::sonic_test::zero_copy_deserialization::Pod>() {}
+            #(assert_pod::<#field_types>();)*
+
+            assert!(
+                ::core::mem::size_of::<#name>() == 0 #(+ ::core::mem::size_of::<#field_types>())*,
+                #padding_message
+            );
+        };
+
+        // Every field is `Pod` and there is no padding (asserted above)
+        unsafe impl ::sonic_test::zero_copy_deserialization::Pod for #name {}
+    })
+}
+
+fn has_stable_repr(input: &DeriveInput) -> syn::Result<bool> {
+    let mut stable = false;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") || meta.path.is_ident("transparent") {
+                stable = true;
+            }
+            // Skip arguments such as `align(8)`
+            if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<proc_macro2::TokenStream>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(stable)
+}